@@ -0,0 +1,778 @@
+use super::{
+    super::{super::RGBAImage, DecodeError, MAX_PIXELS},
+    consts::ZIGZAG,
+    dct_basis,
+    exif::{self, Exif},
+};
+use alloc::vec::Vec;
+use libm::hypotf;
+
+// diagonal of a 35mm film frame, which defines the equivalent focal length
+const FRAME_DIAGONAL_35MM: f32 = 43.27;
+
+pub struct DecodedJpeg {
+    // pixels, already turned upright according to the EXIF orientation
+    pub image: RGBAImage,
+    // the EXIF orientation that was applied, 1-8
+    pub orientation: u8,
+    // camera focal length in pixels, usable as ExtractOptions::focal_length; 0 if unknown
+    pub focal_length: f32,
+}
+
+struct Huffman {
+    // symbol and code length, indexed by the next bits of input
+    lookup: Vec<(u8, u8)>,
+    bits: u32,
+}
+
+impl Huffman {
+    fn new(counts: &[u8], symbols: &[u8]) -> Result<Huffman, DecodeError> {
+        let bits = counts.iter().rposition(|&c| c != 0).map_or(1, |i| i + 1) as u32;
+        let mut lookup = vec![(0, 0); 1 << bits];
+        let mut code = 0usize;
+        let mut k = 0;
+        for (len, &count) in counts.iter().enumerate() {
+            let len = len as u32 + 1;
+            for _ in 0..count {
+                if code >= 1 << len {
+                    return Err(DecodeError::Invalid("oversubscribed Huffman code"));
+                }
+                let shift = bits - len;
+                lookup[code << shift..(code + 1) << shift].fill((symbols[k], len as u8));
+                code += 1;
+                k += 1;
+            }
+            code <<= 1;
+        }
+        Ok(Huffman { lookup, bits })
+    }
+}
+
+// MSB-first reader over entropy-coded data, which stops at the next marker
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    acc: u64,
+    count: u32,
+}
+
+impl<'a> Reader<'a> {
+    fn refill(&mut self) {
+        while self.count <= 56 {
+            let mut byte = 0;
+            // past a marker or the end, the stream is padded with zeros
+            if let Some(&b) = self.data.get(self.pos) {
+                if b != 0xFF {
+                    byte = b;
+                    self.pos += 1;
+                } else if self.data.get(self.pos + 1) == Some(&0) {
+                    byte = 0xFF;
+                    self.pos += 2;
+                }
+            }
+            self.acc |= (byte as u64) << (56 - self.count);
+            self.count += 8;
+        }
+    }
+
+    fn bits(&mut self, n: u32) -> u32 {
+        if n == 0 {
+            return 0;
+        }
+        self.refill();
+        let val = (self.acc >> (64 - n)) as u32;
+        self.acc <<= n;
+        self.count -= n;
+        val
+    }
+
+    fn bit(&mut self) -> bool {
+        self.bits(1) == 1
+    }
+
+    fn decode(&mut self, table: &Huffman) -> Result<u8, DecodeError> {
+        self.refill();
+        let (sym, len) = table.lookup[(self.acc >> (64 - table.bits)) as usize];
+        if len == 0 {
+            return Err(DecodeError::Invalid("Huffman code"));
+        }
+        self.acc <<= len;
+        self.count -= len as u32;
+        Ok(sym)
+    }
+
+    // an s-bit signed coefficient
+    fn extend(&mut self, s: u8) -> i32 {
+        let v = self.bits(s as u32) as i32;
+        if s > 0 && v < 1 << (s - 1) {
+            v - (1 << s) + 1
+        } else {
+            v
+        }
+    }
+
+    // drops buffered bits and skips past the next restart marker
+    fn restart(&mut self) {
+        self.acc = 0;
+        self.count = 0;
+        while self.pos + 1 < self.data.len() {
+            if self.data[self.pos] == 0xFF && (0xD0..=0xD7).contains(&self.data[self.pos + 1]) {
+                self.pos += 2;
+                return;
+            }
+            self.pos += 1;
+        }
+    }
+}
+
+struct Component {
+    id: u8,
+    h: usize,
+    v: usize,
+    quant: usize,
+    // blocks per row and column, padded to whole MCUs
+    bw: usize,
+    bh: usize,
+    // blocks that hold image data
+    cw: usize,
+    ch: usize,
+    // coefficients of each block in natural order
+    coefs: Vec<[i16; 64]>,
+    dc_table: usize,
+    ac_table: usize,
+    pred: i32,
+}
+
+struct Scan {
+    comps: Vec<usize>,
+    // spectral selection and successive approximation
+    ss: usize,
+    se: usize,
+    ah: u8,
+    al: u8,
+}
+
+struct Decoder {
+    progressive: bool,
+    width: usize,
+    height: usize,
+    hmax: usize,
+    vmax: usize,
+    comps: Vec<Component>,
+    quant: [[u16; 64]; 4],
+    dc: [Option<Huffman>; 4],
+    ac: [Option<Huffman>; 4],
+    restart_interval: usize,
+    eobrun: u32,
+}
+
+fn be16(data: &[u8], at: usize) -> Result<usize, DecodeError> {
+    match data.get(at..at + 2) {
+        Some(b) => Ok(u16::from_be_bytes([b[0], b[1]]) as usize),
+        None => Err(DecodeError::Truncated),
+    }
+}
+
+impl Decoder {
+    fn frame(&mut self, seg: &[u8]) -> Result<(), DecodeError> {
+        if !self.comps.is_empty() {
+            return Err(DecodeError::Invalid("multiple frames"));
+        }
+        if seg.len() < 6 {
+            return Err(DecodeError::Truncated);
+        }
+        if seg[0] != 8 {
+            return Err(DecodeError::Unsupported(
+                "sample precision other than 8 bits",
+            ));
+        }
+        self.height = be16(seg, 1)?;
+        self.width = be16(seg, 3)?;
+        let n = seg[5] as usize;
+        if self.width == 0 || self.height == 0 {
+            return Err(DecodeError::Unsupported(
+                "height defined after the first scan",
+            ));
+        }
+        // coefficients for every block are held until the end, so they are allocated up front
+        if self.width * self.height > MAX_PIXELS {
+            return Err(DecodeError::Unsupported("image too large"));
+        }
+        if n != 1 && n != 3 {
+            return Err(DecodeError::Unsupported(
+                "component count other than 1 or 3",
+            ));
+        }
+        if seg.len() < 6 + n * 3 {
+            return Err(DecodeError::Truncated);
+        }
+        for c in seg[6..6 + n * 3].chunks_exact(3) {
+            let (h, v) = ((c[1] >> 4) as usize, (c[1] & 15) as usize);
+            if !(1..=4).contains(&h) || !(1..=4).contains(&v) || c[2] > 3 {
+                return Err(DecodeError::Invalid("component parameters"));
+            }
+            self.comps.push(Component {
+                id: c[0],
+                h,
+                v,
+                quant: c[2] as usize,
+                bw: 0,
+                bh: 0,
+                cw: 0,
+                ch: 0,
+                coefs: Vec::new(),
+                dc_table: 0,
+                ac_table: 0,
+                pred: 0,
+            });
+        }
+        self.hmax = self.comps.iter().map(|c| c.h).max().unwrap();
+        self.vmax = self.comps.iter().map(|c| c.v).max().unwrap();
+        let mcux = self.width.div_ceil(8 * self.hmax);
+        let mcuy = self.height.div_ceil(8 * self.vmax);
+        for c in &mut self.comps {
+            c.bw = mcux * c.h;
+            c.bh = mcuy * c.v;
+            c.cw = (self.width * c.h).div_ceil(self.hmax).div_ceil(8);
+            c.ch = (self.height * c.v).div_ceil(self.vmax).div_ceil(8);
+            c.coefs = vec![[0; 64]; c.bw * c.bh];
+        }
+        Ok(())
+    }
+
+    fn quant_tables(&mut self, mut seg: &[u8]) -> Result<(), DecodeError> {
+        while !seg.is_empty() {
+            let (precision, id) = (seg[0] >> 4, (seg[0] & 15) as usize);
+            let size = if precision == 0 { 64 } else { 128 };
+            if id > 3 || seg.len() < 1 + size {
+                return Err(DecodeError::Invalid("DQT"));
+            }
+            for (k, &z) in ZIGZAG.iter().enumerate() {
+                self.quant[id][z] = if precision == 0 {
+                    seg[1 + k] as u16
+                } else {
+                    u16::from_be_bytes([seg[1 + 2 * k], seg[2 + 2 * k]])
+                };
+            }
+            seg = &seg[1 + size..];
+        }
+        Ok(())
+    }
+
+    fn huffman_tables(&mut self, mut seg: &[u8]) -> Result<(), DecodeError> {
+        while !seg.is_empty() {
+            if seg.len() < 17 {
+                return Err(DecodeError::Truncated);
+            }
+            let (class, id) = (seg[0] >> 4, (seg[0] & 15) as usize);
+            let counts = &seg[1..17];
+            let total: usize = counts.iter().map(|&c| c as usize).sum();
+            if class > 1 || id > 3 || total > 256 || seg.len() < 17 + total {
+                return Err(DecodeError::Invalid("DHT"));
+            }
+            let table = Huffman::new(counts, &seg[17..17 + total])?;
+            if class == 0 {
+                self.dc[id] = Some(table);
+            } else {
+                self.ac[id] = Some(table);
+            }
+            seg = &seg[17 + total..];
+        }
+        Ok(())
+    }
+
+    fn scan_header(&mut self, seg: &[u8]) -> Result<Scan, DecodeError> {
+        if self.comps.is_empty() {
+            return Err(DecodeError::Invalid("scan before frame"));
+        }
+        let n = *seg.first().ok_or(DecodeError::Truncated)? as usize;
+        if n == 0 || n > 4 || seg.len() < 4 + n * 2 {
+            return Err(DecodeError::Invalid("SOS"));
+        }
+        let mut comps = Vec::with_capacity(n);
+        for s in seg[1..1 + n * 2].chunks_exact(2) {
+            let i = self
+                .comps
+                .iter()
+                .position(|c| c.id == s[0])
+                .ok_or(DecodeError::Invalid("scan component"))?;
+            let c = &mut self.comps[i];
+            c.dc_table = (s[1] >> 4) as usize & 3;
+            c.ac_table = (s[1] & 15) as usize & 3;
+            comps.push(i);
+        }
+        let p = &seg[1 + n * 2..];
+        let scan = Scan {
+            comps,
+            ss: p[0] as usize,
+            se: p[1] as usize,
+            ah: p[2] >> 4,
+            al: p[2] & 15,
+        };
+        let valid = if self.progressive {
+            scan.ss <= scan.se
+                && scan.se < 64
+                && (scan.ss == 0) == (scan.se == 0)
+                && (scan.ss == 0 || scan.comps.len() == 1)
+                && scan.al < 14
+        } else {
+            scan.ss == 0 && scan.se == 63 && scan.ah == 0 && scan.al == 0
+        };
+        if !valid {
+            return Err(DecodeError::Invalid("scan parameters"));
+        }
+        let needs_dc = scan.ss == 0 && scan.ah == 0;
+        let needs_ac = scan.se > 0;
+        for &i in &scan.comps {
+            let c = &self.comps[i];
+            if (needs_dc && self.dc[c.dc_table].is_none())
+                || (needs_ac && self.ac[c.ac_table].is_none())
+            {
+                return Err(DecodeError::Invalid("missing Huffman table"));
+            }
+        }
+        Ok(scan)
+    }
+
+    fn block(
+        &mut self,
+        reader: &mut Reader,
+        scan: &Scan,
+        ci: usize,
+        bi: usize,
+    ) -> Result<(), DecodeError> {
+        let c = &mut self.comps[ci];
+        let coefs = &mut c.coefs[bi];
+        if !self.progressive {
+            let t = reader.decode(self.dc[c.dc_table].as_ref().unwrap())?;
+            if t > 11 {
+                return Err(DecodeError::Invalid("DC magnitude"));
+            }
+            c.pred += reader.extend(t);
+            coefs[0] = c.pred as i16;
+            let ac = self.ac[c.ac_table].as_ref().unwrap();
+            let mut k = 1;
+            while k < 64 {
+                let rs = reader.decode(ac)?;
+                let (r, s) = ((rs >> 4) as usize, rs & 15);
+                if s == 0 {
+                    if r < 15 {
+                        break;
+                    }
+                    k += 16;
+                    continue;
+                }
+                k += r;
+                if k > 63 {
+                    return Err(DecodeError::Invalid("AC coefficient index"));
+                }
+                coefs[ZIGZAG[k]] = reader.extend(s) as i16;
+                k += 1;
+            }
+            return Ok(());
+        }
+
+        if scan.ss == 0 {
+            if scan.ah == 0 {
+                let t = reader.decode(self.dc[c.dc_table].as_ref().unwrap())?;
+                if t > 11 {
+                    return Err(DecodeError::Invalid("DC magnitude"));
+                }
+                c.pred += reader.extend(t);
+                coefs[0] = (c.pred << scan.al) as i16;
+            } else if reader.bit() {
+                coefs[0] |= 1 << scan.al;
+            }
+            return Ok(());
+        }
+
+        let ac = self.ac[c.ac_table].as_ref().unwrap();
+        if scan.ah == 0 {
+            // first pass over this band
+            if self.eobrun > 0 {
+                self.eobrun -= 1;
+                return Ok(());
+            }
+            let mut k = scan.ss;
+            while k <= scan.se {
+                let rs = reader.decode(ac)?;
+                let (r, s) = ((rs >> 4) as u32, rs & 15);
+                if s == 0 {
+                    if r < 15 {
+                        self.eobrun = (1 << r) - 1 + reader.bits(r);
+                        break;
+                    }
+                    k += 16;
+                    continue;
+                }
+                k += r as usize;
+                if k > 63 {
+                    return Err(DecodeError::Invalid("AC coefficient index"));
+                }
+                coefs[ZIGZAG[k]] = (reader.extend(s) << scan.al) as i16;
+                k += 1;
+            }
+            return Ok(());
+        }
+
+        // refinement: one more bit for each nonzero coefficient, and newly nonzero ones
+        let p1 = 1i16 << scan.al;
+        let m1 = -1i16 << scan.al;
+        let refine = |reader: &mut Reader, coef: &mut i16| {
+            if reader.bit() && *coef & p1 == 0 {
+                *coef += if *coef >= 0 { p1 } else { m1 };
+            }
+        };
+        let mut k = scan.ss;
+        if self.eobrun == 0 {
+            while k <= scan.se {
+                let rs = reader.decode(ac)?;
+                let (mut r, s) = ((rs >> 4) as i32, rs & 15);
+                let mut val = 0;
+                if s == 0 {
+                    if r < 15 {
+                        self.eobrun = (1 << r) + reader.bits(r as u32);
+                        break;
+                    }
+                } else {
+                    val = if reader.bit() { p1 } else { m1 };
+                }
+                while k <= scan.se {
+                    let coef = &mut coefs[ZIGZAG[k]];
+                    if *coef != 0 {
+                        refine(reader, coef);
+                    } else {
+                        if r == 0 {
+                            break;
+                        }
+                        r -= 1;
+                    }
+                    k += 1;
+                }
+                if val != 0 && k <= scan.se {
+                    coefs[ZIGZAG[k]] = val;
+                }
+                k += 1;
+            }
+        }
+        if self.eobrun > 0 {
+            while k <= scan.se {
+                let coef = &mut coefs[ZIGZAG[k]];
+                if *coef != 0 {
+                    refine(reader, coef);
+                }
+                k += 1;
+            }
+            self.eobrun -= 1;
+        }
+        Ok(())
+    }
+
+    // decodes entropy-coded data for the scan, returning where it ends
+    fn scan(&mut self, data: &[u8], scan: &Scan) -> Result<usize, DecodeError> {
+        let mut reader = Reader {
+            data,
+            pos: 0,
+            acc: 0,
+            count: 0,
+        };
+        for &i in &scan.comps {
+            self.comps[i].pred = 0;
+        }
+        self.eobrun = 0;
+        let mut units = 0;
+        let mut unit = |dec: &mut Decoder, reader: &mut Reader| {
+            if dec.restart_interval > 0 && units > 0 && units % dec.restart_interval == 0 {
+                reader.restart();
+                for &i in &scan.comps {
+                    dec.comps[i].pred = 0;
+                }
+                dec.eobrun = 0;
+            }
+            units += 1;
+        };
+        if scan.comps.len() == 1 {
+            // non-interleaved scans only cover blocks with image data
+            let ci = scan.comps[0];
+            let (cw, ch, bw) = {
+                let c = &self.comps[ci];
+                (c.cw, c.ch, c.bw)
+            };
+            for by in 0..ch {
+                for bx in 0..cw {
+                    unit(self, &mut reader);
+                    self.block(&mut reader, scan, ci, by * bw + bx)?;
+                }
+            }
+        } else {
+            let mcux = self.width.div_ceil(8 * self.hmax);
+            let mcuy = self.height.div_ceil(8 * self.vmax);
+            for my in 0..mcuy {
+                for mx in 0..mcux {
+                    unit(self, &mut reader);
+                    for &ci in &scan.comps {
+                        let (h, v, bw) = {
+                            let c = &self.comps[ci];
+                            (c.h, c.v, c.bw)
+                        };
+                        for y in 0..v {
+                            for x in 0..h {
+                                self.block(&mut reader, scan, ci, (my * v + y) * bw + mx * h + x)?;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        // skip anything left before the next marker
+        let mut pos = reader.pos;
+        while pos + 1 < data.len()
+            && !(data[pos] == 0xFF && data[pos + 1] != 0 && !(0xD0..=0xD7).contains(&data[pos + 1]))
+        {
+            pos += 1;
+        }
+        Ok(pos)
+    }
+
+    // dequantizes and inverse transforms each component into a plane of cw * 8 by ch * 8 samples
+    fn planes(&self) -> Vec<Vec<u8>> {
+        let basis = dct_basis();
+        self.comps
+            .iter()
+            .map(|c| {
+                let q = &self.quant[c.quant];
+                let stride = c.cw * 8;
+                let mut plane = vec![0u8; stride * c.ch * 8];
+                for by in 0..c.ch {
+                    for bx in 0..c.cw {
+                        let coefs = &c.coefs[by * c.bw + bx];
+                        let mut f = [0.0f32; 64];
+                        for i in 0..64 {
+                            f[i] = coefs[i] as f32 * q[i] as f32;
+                        }
+                        let mut out = [0.0f32; 64];
+                        if coefs[1..].iter().all(|&v| v == 0) {
+                            out = [f[0] * 0.125; 64];
+                        } else {
+                            // rows, then columns, of the transposed basis
+                            let mut rows = [0.0f32; 64];
+                            for v in 0..8 {
+                                for x in 0..8 {
+                                    let mut sum = 0.0;
+                                    for u in 0..8 {
+                                        sum += basis[u][x] * f[v * 8 + u];
+                                    }
+                                    rows[v * 8 + x] = sum;
+                                }
+                            }
+                            for y in 0..8 {
+                                for x in 0..8 {
+                                    let mut sum = 0.0;
+                                    for v in 0..8 {
+                                        sum += basis[v][y] * rows[v * 8 + x];
+                                    }
+                                    out[y * 8 + x] = sum;
+                                }
+                            }
+                        }
+                        for y in 0..8 {
+                            let row = (by * 8 + y) * stride + bx * 8;
+                            for x in 0..8 {
+                                plane[row + x] = (out[y * 8 + x] + 128.5).clamp(0.0, 255.0) as u8;
+                            }
+                        }
+                    }
+                }
+                plane
+            })
+            .collect()
+    }
+}
+
+// samples a subsampled plane at full resolution, interpolating between sample centers
+fn upsample(plane: &[u8], stride: usize, rows: usize, c: &Component, dec: &Decoder) -> Vec<u8> {
+    let (w, h) = (dec.width, dec.height);
+    if c.h == dec.hmax && c.v == dec.vmax {
+        let mut out = Vec::with_capacity(w * h);
+        for y in 0..h {
+            out.extend_from_slice(&plane[y * stride..y * stride + w]);
+        }
+        return out;
+    }
+    let sx = c.h as f32 / dec.hmax as f32;
+    let sy = c.v as f32 / dec.vmax as f32;
+    let cols = (w * c.h).div_ceil(dec.hmax).min(stride);
+    let rows = (h * c.v).div_ceil(dec.vmax).min(rows);
+    let mut out = Vec::with_capacity(w * h);
+    for y in 0..h {
+        let fy = ((y as f32 + 0.5) * sy - 0.5).clamp(0.0, (rows - 1) as f32);
+        let y0 = fy as usize;
+        let y1 = (y0 + 1).min(rows - 1);
+        let ty = fy - y0 as f32;
+        for x in 0..w {
+            let fx = ((x as f32 + 0.5) * sx - 0.5).clamp(0.0, (cols - 1) as f32);
+            let x0 = fx as usize;
+            let x1 = (x0 + 1).min(cols - 1);
+            let tx = fx - x0 as f32;
+            let at = |x: usize, y: usize| plane[y * stride + x] as f32;
+            let top = at(x0, y0) * (1.0 - tx) + at(x1, y0) * tx;
+            let bottom = at(x0, y1) * (1.0 - tx) + at(x1, y1) * tx;
+            out.push((top * (1.0 - ty) + bottom * ty + 0.5) as u8);
+        }
+    }
+    out
+}
+
+pub fn decode_jpeg(data: &[u8]) -> Result<DecodedJpeg, DecodeError> {
+    if data.get(..2) != Some(&[0xFF, 0xD8]) {
+        return Err(DecodeError::Invalid("JPEG signature"));
+    }
+    let mut dec = Decoder {
+        progressive: false,
+        width: 0,
+        height: 0,
+        hmax: 1,
+        vmax: 1,
+        comps: Vec::new(),
+        quant: [[1; 64]; 4],
+        dc: [None, None, None, None],
+        ac: [None, None, None, None],
+        restart_interval: 0,
+        eobrun: 0,
+    };
+    let mut exif = Exif::default();
+    // Adobe APP14 color transform flag, if present
+    let mut adobe_transform = None;
+    let mut scanned = false;
+    let mut pos = 2;
+    loop {
+        // markers may be preceded by any number of fill bytes
+        while data.get(pos) == Some(&0xFF) && data.get(pos + 1) == Some(&0xFF) {
+            pos += 1;
+        }
+        let marker = match data.get(pos..pos + 2) {
+            Some(&[0xFF, m]) => m,
+            // a missing EOI after the last scan is common enough to tolerate
+            None if scanned => break,
+            None => return Err(DecodeError::Truncated),
+            _ => return Err(DecodeError::Invalid("expected a marker")),
+        };
+        pos += 2;
+        match marker {
+            0xD9 => break,
+            0xD0..=0xD7 | 0x01 => continue,
+            _ => {}
+        }
+        let len = be16(data, pos)?;
+        let seg = data.get(pos + 2..pos + len).ok_or(DecodeError::Truncated)?;
+        pos += len;
+        match marker {
+            0xC0..=0xC2 => {
+                dec.progressive = marker == 0xC2;
+                dec.frame(seg)?;
+            }
+            0xC3 | 0xC5..=0xC7 | 0xC9..=0xCB | 0xCD..=0xCF => {
+                return Err(DecodeError::Unsupported(
+                    "lossless, hierarchical or arithmetic JPEG",
+                ))
+            }
+            0xC4 => dec.huffman_tables(seg)?,
+            0xDB => dec.quant_tables(seg)?,
+            0xDD => dec.restart_interval = be16(seg, 0)?,
+            0xDA => {
+                let scan = dec.scan_header(seg)?;
+                pos += dec.scan(&data[pos..], &scan)?;
+                scanned = true;
+            }
+            0xE1 if seg.starts_with(b"Exif\0\0") => {
+                exif = exif::parse(&seg[6..]).unwrap_or_default();
+            }
+            0xEE if seg.starts_with(b"Adobe") && seg.len() >= 12 => {
+                adobe_transform = Some(seg[11]);
+            }
+            _ => {}
+        }
+    }
+    if !scanned {
+        return Err(DecodeError::Invalid("no image data"));
+    }
+
+    let planes = dec.planes();
+    let full: Vec<Vec<u8>> = dec
+        .comps
+        .iter()
+        .zip(&planes)
+        .map(|(c, plane)| upsample(plane, c.cw * 8, c.ch * 8, c, &dec))
+        .collect();
+    let (width, height) = (dec.width, dec.height);
+    let mut rgba = Vec::with_capacity(width * height * 4);
+    if full.len() == 1 {
+        for &v in &full[0] {
+            rgba.extend_from_slice(&[v, v, v, 255]);
+        }
+    } else {
+        let ids: Vec<u8> = dec.comps.iter().map(|c| c.id).collect();
+        let rgb = adobe_transform == Some(0) || (adobe_transform.is_none() && ids == b"RGB");
+        for ((&a, &b), &c) in full[0].iter().zip(&full[1]).zip(&full[2]) {
+            let (a, b, c) = (a as f32, b as f32, c as f32);
+            let px = if rgb {
+                [a, b, c]
+            } else {
+                let (cb, cr) = (b - 128.0, c - 128.0);
+                [
+                    a + 1.402 * cr,
+                    a - 0.344136 * cb - 0.714136 * cr,
+                    a + 1.772 * cb,
+                ]
+            };
+            for v in px {
+                rgba.push((v + 0.5).clamp(0.0, 255.0) as u8);
+            }
+            rgba.push(255);
+        }
+    }
+    let image = RGBAImage {
+        data: rgba,
+        width,
+        height,
+    };
+
+    let focal_length = match exif {
+        Exif {
+            focal_length_35mm: Some(f),
+            ..
+        } => f * hypotf(width as f32, height as f32) / FRAME_DIAGONAL_35MM,
+        Exif {
+            focal_length: Some(f),
+            focal_plane_resolution: Some(res),
+            pixel_width,
+            ..
+        } => {
+            // the sensor resolution refers to the image size the camera wrote
+            let scale = pixel_width.map_or(1.0, |pw| width as f32 / pw.max(1) as f32);
+            f * res * scale
+        }
+        _ => 0.0,
+    };
+    let orientation = exif.orientation.max(1);
+    let (turns, mirror) = match orientation {
+        2 => (0, true),
+        3 => (2, false),
+        4 => (2, true),
+        5 => (3, true),
+        6 => (1, false),
+        7 => (1, true),
+        8 => (3, false),
+        _ => (0, false),
+    };
+    let image = if orientation == 1 {
+        image
+    } else {
+        image.rotate(turns, mirror)
+    };
+    Ok(DecodedJpeg {
+        image,
+        orientation,
+        focal_length,
+    })
+}
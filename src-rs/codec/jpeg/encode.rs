@@ -0,0 +1,388 @@
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use super::{
+    super::{
+        super::{Image, RGBAImage},
+        EncodeError,
+    },
+    consts::*,
+    dct_basis,
+};
+use alloc::vec::Vec;
+use libm::roundf;
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum Subsampling {
+    // full resolution chroma
+    S444,
+    // chroma averaged over 2x2 blocks
+    #[default]
+    S420,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Copy)]
+pub struct JpegOptions {
+    // 1-100, scaling the standard quantization tables as libjpeg does
+    pub quality: u8,
+    pub subsampling: Subsampling,
+    // encode only luminance
+    pub grayscale: bool,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl JpegOptions {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new() -> JpegOptions {
+        JpegOptions {
+            quality: 85,
+            subsampling: Subsampling::S420,
+            grayscale: false,
+        }
+    }
+}
+
+impl Default for JpegOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn scale_quant(base: &[u8; 64], quality: u8) -> [u8; 64] {
+    let quality = quality.clamp(1, 100) as u32;
+    let scale = if quality < 50 {
+        5000 / quality
+    } else {
+        200 - quality * 2
+    };
+    let mut out = [0; 64];
+    for (o, &b) in out.iter_mut().zip(base.iter()) {
+        *o = ((b as u32 * scale + 50) / 100).clamp(1, 255) as u8;
+    }
+    out
+}
+
+struct Huffman {
+    // code and length for each symbol
+    codes: [(u16, u8); 256],
+}
+
+impl Huffman {
+    fn new(bits: &[u8; 16], vals: &[u8]) -> Huffman {
+        let mut codes = [(0, 0); 256];
+        let mut code = 0u16;
+        let mut k = 0;
+        for (len, &count) in bits.iter().enumerate() {
+            for _ in 0..count {
+                codes[vals[k] as usize] = (code, len as u8 + 1);
+                code += 1;
+                k += 1;
+            }
+            code <<= 1;
+        }
+        Huffman { codes }
+    }
+}
+
+struct BitWriter {
+    out: Vec<u8>,
+    acc: u32,
+    bits: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u32, len: u32) {
+        self.acc = (self.acc << len) | code;
+        self.bits += len;
+        while self.bits >= 8 {
+            self.bits -= 8;
+            let byte = (self.acc >> self.bits) as u8;
+            self.out.push(byte);
+            if byte == 0xFF {
+                self.out.push(0);
+            }
+        }
+        self.acc &= (1 << self.bits) - 1;
+    }
+
+    fn code(&mut self, table: &Huffman, symbol: u8) {
+        let (code, len) = table.codes[symbol as usize];
+        self.write(code as u32, len as u32);
+    }
+
+    // pads the final byte with ones
+    fn flush(&mut self) {
+        if self.bits > 0 {
+            self.write((1 << (8 - self.bits)) - 1, 8 - self.bits);
+        }
+    }
+}
+
+// magnitude category and the bits that encode a coefficient
+fn category(v: i32) -> (u32, u32) {
+    let size = 32 - v.unsigned_abs().leading_zeros();
+    let bits = (if v < 0 { v - 1 } else { v }) as u32 & ((1 << size) - 1);
+    (size, bits)
+}
+
+struct Component {
+    // quantizer and its reciprocals in natural order
+    quant: [u8; 64],
+    scale: [f32; 64],
+    dc: Huffman,
+    ac: Huffman,
+    pred: i32,
+}
+
+impl Component {
+    fn new(quant: [u8; 64], dc: Huffman, ac: Huffman) -> Component {
+        let mut scale = [0.0; 64];
+        for (s, &q) in scale.iter_mut().zip(quant.iter()) {
+            *s = 1.0 / q as f32;
+        }
+        Component {
+            quant,
+            scale,
+            dc,
+            ac,
+            pred: 0,
+        }
+    }
+}
+
+struct Encoder {
+    dct: [[f32; 8]; 8],
+    writer: BitWriter,
+}
+
+impl Encoder {
+    fn new() -> Encoder {
+        Encoder {
+            dct: dct_basis(),
+            writer: BitWriter {
+                out: Vec::new(),
+                acc: 0,
+                bits: 0,
+            },
+        }
+    }
+
+    // block holds level-shifted samples in row-major order
+    fn block(&mut self, block: &[f32; 64], comp: &mut Component) {
+        let mut rows = [0.0; 64];
+        for y in 0..8 {
+            for u in 0..8 {
+                let mut sum = 0.0;
+                for x in 0..8 {
+                    sum += self.dct[u][x] * block[y * 8 + x];
+                }
+                rows[y * 8 + u] = sum;
+            }
+        }
+        let mut coefs = [0; 64];
+        for u in 0..8 {
+            for v in 0..8 {
+                let mut sum = 0.0;
+                for y in 0..8 {
+                    sum += self.dct[v][y] * rows[y * 8 + u];
+                }
+                let i = v * 8 + u;
+                coefs[i] = roundf(sum * comp.scale[i]) as i32;
+            }
+        }
+
+        let dc = coefs[0];
+        let (size, bits) = category(dc - comp.pred);
+        comp.pred = dc;
+        self.writer.code(&comp.dc, size as u8);
+        self.writer.write(bits, size);
+
+        let mut run = 0;
+        for &z in &ZIGZAG[1..] {
+            let v = coefs[z];
+            if v == 0 {
+                run += 1;
+                continue;
+            }
+            while run > 15 {
+                self.writer.code(&comp.ac, 0xF0);
+                run -= 16;
+            }
+            let (size, bits) = category(v);
+            self.writer.code(&comp.ac, (run << 4) as u8 | size as u8);
+            self.writer.write(bits, size);
+            run = 0;
+        }
+        if run > 0 {
+            self.writer.code(&comp.ac, 0x00);
+        }
+    }
+}
+
+fn marker(out: &mut Vec<u8>, marker: u8, payload: &[u8]) {
+    out.extend_from_slice(&[0xFF, marker]);
+    out.extend_from_slice(&((payload.len() + 2) as u16).to_be_bytes());
+    out.extend_from_slice(payload);
+}
+
+fn huffman_table(out: &mut Vec<u8>, class_id: u8, bits: &[u8; 16], vals: &[u8]) {
+    out.push(class_id);
+    out.extend_from_slice(bits);
+    out.extend_from_slice(vals);
+}
+
+// baseline encoding of width x height pixels, given as level-shifted Y, Cb, Cr
+fn encode<F: Fn(usize, usize) -> [f32; 3]>(
+    width: usize,
+    height: usize,
+    options: &JpegOptions,
+    pixel: F,
+) -> Result<Vec<u8>, EncodeError> {
+    if !(1..=0xFFFF).contains(&width) || !(1..=0xFFFF).contains(&height) {
+        return Err(EncodeError::Size("JPEG dimensions must be 1-65535"));
+    }
+    let color = !options.grayscale;
+    let half = color && options.subsampling == Subsampling::S420;
+    let mut luma = Component::new(
+        scale_quant(&LUMA_QUANT, options.quality),
+        Huffman::new(&LUMA_DC_BITS, &LUMA_DC_VALS),
+        Huffman::new(&LUMA_AC_BITS, &LUMA_AC_VALS),
+    );
+    let chroma_quant = scale_quant(&CHROMA_QUANT, options.quality);
+    let mut chroma = [0, 1].map(|_| {
+        Component::new(
+            chroma_quant,
+            Huffman::new(&CHROMA_DC_BITS, &CHROMA_DC_VALS),
+            Huffman::new(&CHROMA_AC_BITS, &CHROMA_AC_VALS),
+        )
+    });
+
+    let mut enc = Encoder::new();
+    let out = &mut enc.writer.out;
+    out.extend_from_slice(&[0xFF, 0xD8]);
+    marker(
+        out,
+        0xE0,
+        &[b'J', b'F', b'I', b'F', 0, 1, 1, 0, 0, 1, 0, 1, 0, 0],
+    );
+
+    // tables are stored in zigzag order
+    let mut dqt = vec![0];
+    dqt.extend(ZIGZAG.iter().map(|&z| luma.quant[z]));
+    if color {
+        dqt.push(1);
+        dqt.extend(ZIGZAG.iter().map(|&z| chroma[0].quant[z]));
+    }
+    marker(out, 0xDB, &dqt);
+
+    let mut sof = vec![8];
+    sof.extend_from_slice(&(height as u16).to_be_bytes());
+    sof.extend_from_slice(&(width as u16).to_be_bytes());
+    if color {
+        let sampling = if half { 0x22 } else { 0x11 };
+        sof.extend_from_slice(&[3, 1, sampling, 0, 2, 0x11, 1, 3, 0x11, 1]);
+    } else {
+        sof.extend_from_slice(&[1, 1, 0x11, 0]);
+    }
+    marker(out, 0xC0, &sof);
+
+    let mut dht = Vec::new();
+    huffman_table(&mut dht, 0x00, &LUMA_DC_BITS, &LUMA_DC_VALS);
+    huffman_table(&mut dht, 0x10, &LUMA_AC_BITS, &LUMA_AC_VALS);
+    if color {
+        huffman_table(&mut dht, 0x01, &CHROMA_DC_BITS, &CHROMA_DC_VALS);
+        huffman_table(&mut dht, 0x11, &CHROMA_AC_BITS, &CHROMA_AC_VALS);
+    }
+    marker(out, 0xC4, &dht);
+
+    if color {
+        marker(out, 0xDA, &[3, 1, 0x00, 2, 0x11, 3, 0x11, 0, 63, 0]);
+    } else {
+        marker(out, 0xDA, &[1, 1, 0x00, 0, 63, 0]);
+    }
+
+    // edge pixels are replicated to fill partial blocks
+    let at = |x: usize, y: usize| pixel(x.min(width - 1), y.min(height - 1));
+    let mcu = if half { 16 } else { 8 };
+    let mut block = [0.0; 64];
+    for my in (0..height).step_by(mcu) {
+        for mx in (0..width).step_by(mcu) {
+            if half {
+                let mut cb = [0.0; 64];
+                let mut cr = [0.0; 64];
+                for by in [0, 8] {
+                    for bx in [0, 8] {
+                        for y in 0..8 {
+                            for x in 0..8 {
+                                let px = at(mx + bx + x, my + by + y);
+                                block[y * 8 + x] = px[0];
+                                let i = ((by + y) >> 1) * 8 + ((bx + x) >> 1);
+                                cb[i] += px[1] * 0.25;
+                                cr[i] += px[2] * 0.25;
+                            }
+                        }
+                        enc.block(&block, &mut luma);
+                    }
+                }
+                enc.block(&cb, &mut chroma[0]);
+                enc.block(&cr, &mut chroma[1]);
+            } else {
+                let mut cb = [0.0; 64];
+                let mut cr = [0.0; 64];
+                for y in 0..8 {
+                    for x in 0..8 {
+                        let px = at(mx + x, my + y);
+                        block[y * 8 + x] = px[0];
+                        cb[y * 8 + x] = px[1];
+                        cr[y * 8 + x] = px[2];
+                    }
+                }
+                enc.block(&block, &mut luma);
+                if color {
+                    enc.block(&cb, &mut chroma[0]);
+                    enc.block(&cr, &mut chroma[1]);
+                }
+            }
+        }
+    }
+    enc.writer.flush();
+    let mut out = enc.writer.out;
+    out.extend_from_slice(&[0xFF, 0xD9]);
+    Ok(out)
+}
+
+// alpha is composited onto white
+pub fn encode_jpeg(image: &RGBAImage, options: &JpegOptions) -> Result<Vec<u8>, EncodeError> {
+    encode(image.width, image.height, options, |x, y| {
+        let base = (y * image.width + x) << 2;
+        let px = &image.data[base..base + 4];
+        let alpha = px[3] as f32 / 255.0;
+        let white = 255.0 * (1.0 - alpha);
+        let r = px[0] as f32 * alpha + white;
+        let g = px[1] as f32 * alpha + white;
+        let b = px[2] as f32 * alpha + white;
+        [
+            0.299 * r + 0.587 * g + 0.114 * b - 128.0,
+            -0.168736 * r - 0.331264 * g + 0.5 * b,
+            0.5 * r - 0.418688 * g - 0.081312 * b,
+        ]
+    })
+}
+
+// single channel encoding of an image with values from 0 to 1
+pub fn encode_jpeg_gray(image: &Image, quality: u8) -> Result<Vec<u8>, EncodeError> {
+    let options = JpegOptions {
+        quality,
+        grayscale: true,
+        ..JpegOptions::new()
+    };
+    encode(image.width, image.height, &options, |x, y| {
+        [
+            image.data[y * image.width + x].clamp(0.0, 1.0) * 255.0 - 128.0,
+            0.0,
+            0.0,
+        ]
+    })
+}
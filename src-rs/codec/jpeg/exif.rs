@@ -0,0 +1,129 @@
+// the few EXIF fields that matter for scanning, from an APP1 segment after its Exif header
+#[derive(Clone, Copy, Default)]
+pub struct Exif {
+    // 1-8 as in the TIFF Orientation tag; 0 if absent
+    pub orientation: u8,
+    // lens focal length in millimeters
+    pub focal_length: Option<f32>,
+    // focal length of the equivalent lens for a 35mm frame
+    pub focal_length_35mm: Option<f32>,
+    // sensor pixels per millimeter, and the image width they refer to
+    pub focal_plane_resolution: Option<f32>,
+    pub pixel_width: Option<u32>,
+}
+
+const ORIENTATION: u16 = 0x0112;
+const EXIF_IFD: u16 = 0x8769;
+const FOCAL_LENGTH: u16 = 0x920A;
+const FOCAL_LENGTH_35MM: u16 = 0xA405;
+const FOCAL_PLANE_X_RESOLUTION: u16 = 0xA20E;
+const FOCAL_PLANE_RESOLUTION_UNIT: u16 = 0xA210;
+const PIXEL_X_DIMENSION: u16 = 0xA002;
+
+struct Tiff<'a> {
+    data: &'a [u8],
+    little: bool,
+}
+
+impl<'a> Tiff<'a> {
+    // offsets come from the file, so they can be anywhere up to u32::MAX and must not wrap
+    fn bytes(&self, at: usize, len: usize) -> Option<&[u8]> {
+        self.data.get(at..at.checked_add(len)?)
+    }
+
+    fn u16(&self, at: usize) -> Option<u16> {
+        let b = self.bytes(at, 2)?;
+        Some(if self.little {
+            u16::from_le_bytes([b[0], b[1]])
+        } else {
+            u16::from_be_bytes([b[0], b[1]])
+        })
+    }
+
+    fn u32(&self, at: usize) -> Option<u32> {
+        let b = self.bytes(at, 4)?;
+        Some(if self.little {
+            u32::from_le_bytes([b[0], b[1], b[2], b[3]])
+        } else {
+            u32::from_be_bytes([b[0], b[1], b[2], b[3]])
+        })
+    }
+
+    // value of a SHORT or LONG entry
+    fn int(&self, entry: usize) -> Option<u32> {
+        let value = entry.checked_add(8)?;
+        match self.u16(entry.checked_add(2)?)? {
+            3 => self.u16(value).map(|v| v as u32),
+            4 => self.u32(value),
+            _ => None,
+        }
+    }
+
+    // value of a RATIONAL entry, which is always stored out of line
+    fn rational(&self, entry: usize) -> Option<f32> {
+        if self.u16(entry.checked_add(2)?)? != 5 {
+            return None;
+        }
+        let at = self.u32(entry.checked_add(8)?)? as usize;
+        let den = self.u32(at.checked_add(4)?)?;
+        if den == 0 {
+            return None;
+        }
+        Some(self.u32(at)? as f32 / den as f32)
+    }
+
+    // offsets of the 12-byte entries of the IFD at the given offset
+    fn entries(&self, ifd: usize) -> impl Iterator<Item = (u16, usize)> + '_ {
+        let count = self.u16(ifd).unwrap_or(0) as usize;
+        (0..count).map_while(move |i| {
+            let entry = ifd.checked_add(2 + i * 12)?;
+            Some((self.u16(entry)?, entry))
+        })
+    }
+}
+
+pub fn parse(data: &[u8]) -> Option<Exif> {
+    let little = match data.get(..4)? {
+        [b'I', b'I', 42, 0] => true,
+        [b'M', b'M', 0, 42] => false,
+        _ => return None,
+    };
+    let tiff = Tiff { data, little };
+    let mut exif = Exif::default();
+    let mut sub_ifd = None;
+    for (tag, entry) in tiff.entries(tiff.u32(4)? as usize) {
+        match tag {
+            ORIENTATION => {
+                // values outside the defined eight mean no transform rather than a guess
+                exif.orientation =
+                    tiff.int(entry).filter(|o| (1..=8).contains(o)).unwrap_or(1) as u8
+            }
+            EXIF_IFD => sub_ifd = tiff.int(entry),
+            _ => {}
+        }
+    }
+    if let Some(ifd) = sub_ifd {
+        let mut resolution = None;
+        let mut unit = 2;
+        for (tag, entry) in tiff.entries(ifd as usize) {
+            match tag {
+                FOCAL_LENGTH => exif.focal_length = tiff.rational(entry),
+                FOCAL_LENGTH_35MM => {
+                    exif.focal_length_35mm = tiff.int(entry).filter(|&f| f > 0).map(|f| f as f32)
+                }
+                FOCAL_PLANE_X_RESOLUTION => resolution = tiff.rational(entry),
+                FOCAL_PLANE_RESOLUTION_UNIT => unit = tiff.int(entry).unwrap_or(2),
+                PIXEL_X_DIMENSION => exif.pixel_width = tiff.int(entry),
+                _ => {}
+            }
+        }
+        let mm = match unit {
+            3 => 10.0,
+            4 => 1.0,
+            5 => 0.001,
+            _ => 25.4,
+        };
+        exif.focal_plane_resolution = resolution.filter(|&r| r > 0.0).map(|r| r / mm);
+    }
+    Some(exif)
+}
@@ -0,0 +1,123 @@
+use core::f32::consts::PI;
+use libm::{cosf, sqrtf};
+
+mod consts;
+mod decode;
+mod encode;
+mod exif;
+pub use decode::*;
+pub use encode::*;
+
+// basis[u][x] = C(u) / 2 * cos((2x + 1) u pi / 16), so the DCT of a row is basis * row
+fn dct_basis() -> [[f32; 8]; 8] {
+    let mut basis = [[0.0; 8]; 8];
+    for (u, row) in basis.iter_mut().enumerate() {
+        let c = if u == 0 { sqrtf(0.5) } else { 1.0 };
+        for (x, v) in row.iter_mut().enumerate() {
+            *v = 0.5 * c * cosf((2 * x + 1) as f32 * u as f32 * PI / 16.0);
+        }
+    }
+    basis
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::{EncodeError, Image, RGBAImage};
+    use super::{decode_jpeg, encode_jpeg, encode_jpeg_gray, JpegOptions, Subsampling};
+    use alloc::vec::Vec;
+    use libm::{cosf, sinf};
+
+    // smooth color with some detail, at a size that isn't a multiple of the block size
+    fn sample(width: usize, height: usize) -> RGBAImage {
+        let mut data = Vec::with_capacity(width * height * 4);
+        for y in 0..height {
+            for x in 0..width {
+                let (fx, fy) = (x as f32, y as f32);
+                data.extend_from_slice(&[
+                    (128.0 + 100.0 * sinf(fx * 0.05)) as u8,
+                    (128.0 + 100.0 * cosf(fy * 0.07)) as u8,
+                    (128.0 + 100.0 * sinf((fx + fy) * 0.03)) as u8,
+                    255,
+                ]);
+            }
+        }
+        RGBAImage {
+            data,
+            width,
+            height,
+        }
+    }
+
+    // mean and largest absolute difference over the color channels
+    fn error(a: &[u8], b: &[u8]) -> (f32, u8) {
+        let mut sum = 0;
+        let mut max = 0;
+        let mut count = 0;
+        for (pa, pb) in a.chunks_exact(4).zip(b.chunks_exact(4)) {
+            for i in 0..3 {
+                let d = pa[i].abs_diff(pb[i]);
+                sum += d as u32;
+                max = max.max(d);
+                count += 1;
+            }
+        }
+        (sum as f32 / count as f32, max)
+    }
+
+    #[test]
+    fn round_trips_color() {
+        let image = sample(61, 45);
+        for subsampling in [Subsampling::S444, Subsampling::S420] {
+            let options = JpegOptions {
+                quality: 90,
+                subsampling,
+                ..JpegOptions::new()
+            };
+            let decoded = decode_jpeg(&encode_jpeg(&image, &options).unwrap())
+                .unwrap()
+                .image;
+            assert_eq!((decoded.width, decoded.height), (61, 45));
+            let (mean, max) = error(&image.data, &decoded.data);
+            assert!(mean < 2.0 && max <= 12, "mean {} max {}", mean, max);
+        }
+    }
+
+    #[test]
+    fn round_trips_gray() {
+        let image = sample(61, 45).to_grayscale();
+        let decoded = decode_jpeg(&encode_jpeg_gray(&image, 90).unwrap())
+            .unwrap()
+            .image;
+        assert_eq!((decoded.width, decoded.height), (61, 45));
+        let expected: Vec<u8> = image
+            .to_gray8()
+            .data
+            .iter()
+            .flat_map(|&v| [v, v, v, 255])
+            .collect();
+        let (mean, max) = error(&expected, &decoded.data);
+        assert!(mean < 1.0 && max <= 6, "mean {} max {}", mean, max);
+    }
+
+    #[test]
+    fn rejects_bad_sizes() {
+        let empty = Image {
+            data: Vec::new(),
+            width: 0,
+            height: 0,
+        };
+        assert!(matches!(
+            encode_jpeg_gray(&empty, 90),
+            Err(EncodeError::Size(_))
+        ));
+        let wide = Image {
+            data: vec![0.0; 0x10000],
+            width: 0x10000,
+            height: 1,
+        };
+        assert!(matches!(
+            encode_jpeg_gray(&wide, 90),
+            Err(EncodeError::Size(_))
+        ));
+    }
+}
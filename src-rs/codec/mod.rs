@@ -0,0 +1,54 @@
+use core::fmt;
+
+mod jpeg;
+mod png;
+mod zlib;
+pub use jpeg::*;
+pub use png::*;
+pub use zlib::{compress, decompress};
+
+// largest image the decoders accept, so a small file can't claim a size that exhausts memory
+const MAX_PIXELS: usize = 1 << 26;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    // the input ended early
+    Truncated,
+    // the input is not valid for the format
+    Invalid(&'static str),
+    // the input is valid but uses a feature the decoder lacks
+    Unsupported(&'static str),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::Truncated => write!(f, "unexpected end of data"),
+            DecodeError::Invalid(what) => write!(f, "invalid data: {}", what),
+            DecodeError::Unsupported(what) => write!(f, "unsupported: {}", what),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DecodeError {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EncodeError {
+    // the format can't store an image of this size, including an empty one
+    Size(&'static str),
+    // an option is out of range
+    Invalid(&'static str),
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EncodeError::Size(what) => write!(f, "unsupported size: {}", what),
+            EncodeError::Invalid(what) => write!(f, "invalid option: {}", what),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for EncodeError {}
@@ -0,0 +1,266 @@
+use super::{
+    super::{super::RGBAImage, zlib, DecodeError, MAX_PIXELS},
+    crc, paeth, SIGNATURE,
+};
+use alloc::vec::Vec;
+
+// x, y offset and step of each Adam7 pass
+const ADAM7: [(usize, usize, usize, usize); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+struct Header {
+    width: usize,
+    height: usize,
+    depth: u8,
+    color_type: u8,
+    channels: usize,
+    interlaced: bool,
+}
+
+fn be32(data: &[u8]) -> u32 {
+    u32::from_be_bytes([data[0], data[1], data[2], data[3]])
+}
+
+fn header(data: &[u8]) -> Result<Header, DecodeError> {
+    if data.len() != 13 {
+        return Err(DecodeError::Invalid("IHDR length"));
+    }
+    let width = be32(data) as usize;
+    let height = be32(&data[4..]) as usize;
+    let (depth, color_type) = (data[8], data[9]);
+    let channels = match (color_type, depth) {
+        (0, 1 | 2 | 4 | 8 | 16) => 1,
+        (3, 1 | 2 | 4 | 8) => 1,
+        (2, 8 | 16) => 3,
+        (4, 8 | 16) => 2,
+        (6, 8 | 16) => 4,
+        _ => return Err(DecodeError::Invalid("color type and bit depth")),
+    };
+    if width == 0 || height == 0 {
+        return Err(DecodeError::Invalid("empty image"));
+    }
+    if width.checked_mul(height).is_none_or(|n| n > MAX_PIXELS) {
+        return Err(DecodeError::Unsupported("image too large"));
+    }
+    if data[10] != 0 || data[11] != 0 {
+        return Err(DecodeError::Unsupported("compression or filter method"));
+    }
+    if data[12] > 1 {
+        return Err(DecodeError::Invalid("interlace method"));
+    }
+    Ok(Header {
+        width,
+        height,
+        depth,
+        color_type,
+        channels,
+        interlaced: data[12] == 1,
+    })
+}
+
+// reverses the filters in place, returning the unfiltered rows without their filter bytes
+fn unfilter(data: &mut [u8], rows: usize, stride: usize, bpp: usize) -> Result<(), DecodeError> {
+    for y in 0..rows {
+        let (before, rest) = data.split_at_mut(y * (stride + 1));
+        let kind = rest[0];
+        let row = &mut rest[1..stride + 1];
+        let up = if y == 0 {
+            None
+        } else {
+            Some(&before[before.len() - stride..])
+        };
+        for i in 0..stride {
+            let a = if i >= bpp { row[i - bpp] } else { 0 };
+            let b = up.map_or(0, |up| up[i]);
+            let c = if i >= bpp {
+                up.map_or(0, |up| up[i - bpp])
+            } else {
+                0
+            };
+            row[i] = row[i].wrapping_add(match kind {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => ((a as u16 + b as u16) >> 1) as u8,
+                4 => paeth(a, b, c),
+                _ => return Err(DecodeError::Invalid("filter type")),
+            });
+        }
+    }
+    Ok(())
+}
+
+pub fn decode_png(data: &[u8]) -> Result<RGBAImage, DecodeError> {
+    if data.len() < 8 || data[..8] != SIGNATURE {
+        return Err(DecodeError::Invalid("PNG signature"));
+    }
+    let mut pos = 8;
+    let mut head = None;
+    let mut palette: Vec<[u8; 4]> = Vec::new();
+    let mut transparent: Option<[u16; 3]> = None;
+    let mut idat = Vec::new();
+    loop {
+        let len = be32(data.get(pos..pos + 4).ok_or(DecodeError::Truncated)?) as usize;
+        if len > 0x7FFFFFFF {
+            return Err(DecodeError::Invalid("chunk length"));
+        }
+        let body = data
+            .get(pos + 4..pos + 12 + len)
+            .ok_or(DecodeError::Truncated)?;
+        let (kind, rest) = body.split_at(4);
+        let (chunk, check) = rest.split_at(len);
+        if be32(check) != crc(kind, chunk) {
+            return Err(DecodeError::Invalid("chunk CRC"));
+        }
+        pos += 12 + len;
+        if head.is_none() && kind != b"IHDR" {
+            return Err(DecodeError::Invalid("missing IHDR"));
+        }
+        match kind {
+            b"IHDR" => head = Some(header(chunk)?),
+            b"PLTE" => {
+                if !len.is_multiple_of(3) || len > 768 {
+                    return Err(DecodeError::Invalid("PLTE length"));
+                }
+                palette = chunk
+                    .chunks_exact(3)
+                    .map(|c| [c[0], c[1], c[2], 255])
+                    .collect();
+            }
+            b"tRNS" => match head.as_ref().map(|h| h.color_type) {
+                Some(3) => {
+                    for (entry, &alpha) in palette.iter_mut().zip(chunk) {
+                        entry[3] = alpha;
+                    }
+                }
+                Some(0) if len == 2 => {
+                    let v = u16::from_be_bytes([chunk[0], chunk[1]]);
+                    transparent = Some([v; 3]);
+                }
+                Some(2) if len == 6 => {
+                    transparent =
+                        Some([0, 2, 4].map(|i| u16::from_be_bytes([chunk[i], chunk[i + 1]])));
+                }
+                _ => return Err(DecodeError::Invalid("tRNS")),
+            },
+            b"IDAT" => idat.extend_from_slice(chunk),
+            b"IEND" => break,
+            _ if kind[0] & 0x20 == 0 => return Err(DecodeError::Unsupported("critical chunk")),
+            _ => {}
+        }
+    }
+    let head = head.unwrap();
+    if head.color_type == 3 && palette.is_empty() {
+        return Err(DecodeError::Invalid("missing PLTE"));
+    }
+
+    let Header {
+        width,
+        height,
+        depth,
+        color_type,
+        channels,
+        interlaced,
+    } = head;
+    let bits = channels * depth as usize;
+    let bpp = (bits / 8).max(1);
+    let passes: &[(usize, usize, usize, usize)] = if interlaced { &ADAM7 } else { &[(0, 0, 1, 1)] };
+    // width, height, row length and filtered size of each pass that has pixels
+    let pass_sizes = passes.iter().map(|&(x0, y0, dx, dy)| {
+        if x0 >= width || y0 >= height {
+            return (0, 0, 0, 0);
+        }
+        let pw = (width - x0).div_ceil(dx);
+        let ph = (height - y0).div_ceil(dy);
+        let stride = (pw * bits).div_ceil(8);
+        (pw, ph, stride, (stride + 1) * ph)
+    });
+    // the header fixes exactly how much data there is, so nothing is allocated before it arrives
+    let expected = pass_sizes.clone().map(|(.., size)| size).sum();
+    let mut raw = zlib::decompress_limited(&idat, expected)?;
+    if raw.len() < expected {
+        return Err(DecodeError::Truncated);
+    }
+    let mut out = vec![0u8; width * height * 4];
+    let max = (1u32 << depth) - 1;
+    let mut offset = 0;
+    for (&(x0, y0, dx, dy), (pw, ph, stride, size)) in passes.iter().zip(pass_sizes) {
+        if size == 0 {
+            continue;
+        }
+        let pass = &mut raw[offset..offset + size];
+        unfilter(pass, ph, stride, bpp)?;
+        for py in 0..ph {
+            let row = &pass[py * (stride + 1) + 1..(py + 1) * (stride + 1)];
+            // sample k of the row at full precision
+            let sample = |k: usize| -> u32 {
+                match depth {
+                    16 => u16::from_be_bytes([row[k * 2], row[k * 2 + 1]]) as u32,
+                    8 => row[k] as u32,
+                    _ => {
+                        let bit = k * depth as usize;
+                        (row[bit >> 3] as u32 >> (8 - depth as usize - (bit & 7))) & max
+                    }
+                }
+            };
+            let to8 = |v: u32| {
+                if depth == 16 {
+                    (v >> 8) as u8
+                } else {
+                    (v * 255 / max) as u8
+                }
+            };
+            for px in 0..pw {
+                let k = px * channels;
+                let rgba = match color_type {
+                    0 => {
+                        let v = sample(k);
+                        let alpha = if transparent.is_some_and(|t| t[0] as u32 == v) {
+                            0
+                        } else {
+                            255
+                        };
+                        let v = to8(v);
+                        [v, v, v, alpha]
+                    }
+                    2 => {
+                        let rgb = [sample(k), sample(k + 1), sample(k + 2)];
+                        let alpha = if transparent
+                            .is_some_and(|t| t.iter().zip(&rgb).all(|(&t, &c)| t as u32 == c))
+                        {
+                            0
+                        } else {
+                            255
+                        };
+                        [to8(rgb[0]), to8(rgb[1]), to8(rgb[2]), alpha]
+                    }
+                    3 => *palette
+                        .get(sample(k) as usize)
+                        .ok_or(DecodeError::Invalid("palette index"))?,
+                    4 => {
+                        let v = to8(sample(k));
+                        [v, v, v, to8(sample(k + 1))]
+                    }
+                    _ => [0, 1, 2, 3].map(|i| to8(sample(k + i))),
+                };
+                let x = x0 + px * dx;
+                let y = y0 + py * dy;
+                let base = (y * width + x) << 2;
+                out[base..base + 4].copy_from_slice(&rgba);
+            }
+        }
+        offset += size;
+    }
+    Ok(RGBAImage {
+        data: out,
+        width,
+        height,
+    })
+}
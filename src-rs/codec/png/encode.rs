@@ -0,0 +1,180 @@
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use super::{
+    super::{
+        super::{BitImage, GrayImage, RGBAImage},
+        zlib, EncodeError,
+    },
+    crc, paeth, SIGNATURE,
+};
+use alloc::vec::Vec;
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum PngColor {
+    // luminance only; color and alpha are flattened onto white
+    Gray,
+    // alpha is flattened onto white
+    Rgb,
+    #[default]
+    Rgba,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Copy)]
+pub struct PngOptions {
+    pub color: PngColor,
+    // zlib level, 0 (none) to 9 (smallest)
+    pub compression: u8,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl PngOptions {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new() -> PngOptions {
+        PngOptions {
+            color: PngColor::Rgba,
+            compression: 6,
+        }
+    }
+}
+
+impl Default for PngOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    out.extend_from_slice(&crc(kind, data).to_be_bytes());
+}
+
+// picks the filter with the smallest sum of absolute differences for each row, as libpng does
+fn filter(raw: &[u8], stride: usize, bpp: usize) -> Vec<u8> {
+    let rows = raw.len() / stride;
+    let mut out = Vec::with_capacity((stride + 1) * rows);
+    let zero = vec![0; stride];
+    let mut candidate = vec![0; stride];
+    let mut best = vec![0; stride];
+    for y in 0..rows {
+        let row = &raw[y * stride..(y + 1) * stride];
+        let up = if y == 0 {
+            &zero[..]
+        } else {
+            &raw[(y - 1) * stride..y * stride]
+        };
+        let mut best_kind = 0;
+        let mut best_cost = u64::MAX;
+        for kind in 0..5u8 {
+            for i in 0..stride {
+                let a = if i >= bpp { row[i - bpp] } else { 0 };
+                let c = if i >= bpp { up[i - bpp] } else { 0 };
+                let b = up[i];
+                candidate[i] = row[i].wrapping_sub(match kind {
+                    0 => 0,
+                    1 => a,
+                    2 => b,
+                    3 => ((a as u16 + b as u16) >> 1) as u8,
+                    _ => paeth(a, b, c),
+                });
+            }
+            let cost = candidate
+                .iter()
+                .map(|&v| (v as i8).unsigned_abs() as u64)
+                .sum();
+            if cost < best_cost {
+                best_cost = cost;
+                best_kind = kind;
+                core::mem::swap(&mut best, &mut candidate);
+            }
+        }
+        out.push(best_kind);
+        out.extend_from_slice(&best);
+    }
+    out
+}
+
+// raw holds unfiltered rows of stride bytes
+fn png(
+    width: usize,
+    height: usize,
+    color_type: u8,
+    depth: u8,
+    raw: &[u8],
+    compression: u8,
+) -> Result<Vec<u8>, EncodeError> {
+    if !(1..=0x7FFFFFFF).contains(&width) || !(1..=0x7FFFFFFF).contains(&height) {
+        return Err(EncodeError::Size("PNG dimensions must be 1-2147483647"));
+    }
+    let stride = raw.len() / height;
+    let filtered = if depth < 8 {
+        // filtering rarely helps packed samples
+        let mut out = Vec::with_capacity((stride + 1) * height);
+        for row in raw.chunks_exact(stride) {
+            out.push(0);
+            out.extend_from_slice(row);
+        }
+        out
+    } else {
+        let channels = match color_type {
+            0 => 1,
+            2 => 3,
+            _ => 4,
+        };
+        filter(raw, stride, channels)
+    };
+
+    let mut out = SIGNATURE.to_vec();
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&(width as u32).to_be_bytes());
+    ihdr.extend_from_slice(&(height as u32).to_be_bytes());
+    ihdr.extend_from_slice(&[depth, color_type, 0, 0, 0]);
+    chunk(&mut out, b"IHDR", &ihdr);
+    chunk(&mut out, b"IDAT", &zlib::compress(&filtered, compression));
+    chunk(&mut out, b"IEND", &[]);
+    Ok(out)
+}
+
+pub fn encode_png(image: &RGBAImage, options: &PngOptions) -> Result<Vec<u8>, EncodeError> {
+    let flatten = |px: &[u8]| {
+        let alpha = px[3] as u32;
+        let white = 255 * (255 - alpha);
+        [0, 1, 2].map(|i| ((px[i] as u32 * alpha + white + 127) / 255) as u8)
+    };
+    let (color_type, raw) = match options.color {
+        PngColor::Rgba => (6, image.data.clone()),
+        PngColor::Rgb => (2, image.data.chunks_exact(4).flat_map(flatten).collect()),
+        PngColor::Gray => (
+            0,
+            image
+                .data
+                .chunks_exact(4)
+                .map(|px| {
+                    let [r, g, b] = flatten(px);
+                    ((r as u32 * 299 + g as u32 * 587 + b as u32 * 114 + 500) / 1000) as u8
+                })
+                .collect(),
+        ),
+    };
+    png(
+        image.width,
+        image.height,
+        color_type,
+        8,
+        &raw,
+        options.compression,
+    )
+}
+
+pub fn encode_png_gray(image: &GrayImage, compression: u8) -> Result<Vec<u8>, EncodeError> {
+    png(image.width, image.height, 0, 8, &image.data, compression)
+}
+
+// 1-bit grayscale, which is the same layout as BitImage
+pub fn encode_png_bits(image: &BitImage, compression: u8) -> Result<Vec<u8>, EncodeError> {
+    png(image.width, image.height, 0, 1, &image.data, compression)
+}
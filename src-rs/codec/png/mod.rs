@@ -0,0 +1,133 @@
+mod decode;
+mod encode;
+pub use decode::*;
+pub use encode::*;
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut c = i as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 {
+                0xEDB88320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            k += 1;
+        }
+        table[i] = c;
+        i += 1;
+    }
+    table
+}
+
+const CRC_TABLE: [u32; 256] = crc_table();
+
+// CRC of the chunk type followed by its data
+fn crc(kind: &[u8], data: &[u8]) -> u32 {
+    let mut c = !0u32;
+    for &byte in kind.iter().chain(data) {
+        c = CRC_TABLE[((c ^ byte as u32) & 0xFF) as usize] ^ (c >> 8);
+    }
+    !c
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let pa = (p - a as i16).abs();
+    let pb = (p - b as i16).abs();
+    let pc = (p - c as i16).abs();
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::{BitImage, EncodeError, GrayImage, RGBAImage};
+    use super::{decode_png, encode_png, encode_png_bits, encode_png_gray, PngOptions};
+    use alloc::vec::Vec;
+
+    fn noise(len: usize, seed: u32) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+                (state >> 24) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn round_trips_rgba() {
+        let image = RGBAImage {
+            data: noise(37 * 23 * 4, 1),
+            width: 37,
+            height: 23,
+        };
+        for compression in [0, 6, 9] {
+            let options = PngOptions {
+                compression,
+                ..PngOptions::new()
+            };
+            let decoded = decode_png(&encode_png(&image, &options).unwrap()).unwrap();
+            assert_eq!((decoded.width, decoded.height), (37, 23));
+            assert_eq!(decoded.data, image.data);
+        }
+    }
+
+    #[test]
+    fn round_trips_gray() {
+        let image = GrayImage {
+            data: noise(41 * 19, 2),
+            width: 41,
+            height: 19,
+        };
+        let decoded = decode_png(&encode_png_gray(&image, 6).unwrap()).unwrap();
+        assert_eq!((decoded.width, decoded.height), (41, 19));
+        let expected: Vec<u8> = image.data.iter().flat_map(|&v| [v, v, v, 255]).collect();
+        assert_eq!(decoded.data, expected);
+    }
+
+    #[test]
+    fn round_trips_bits() {
+        // a width that leaves padding bits at the end of each row
+        let (width, height) = (13usize, 7);
+        let image = BitImage {
+            data: noise(width.div_ceil(8) * height, 3),
+            width,
+            height,
+        };
+        let decoded = decode_png(&encode_png_bits(&image, 9).unwrap()).unwrap();
+        assert_eq!((decoded.width, decoded.height), (width, height));
+        for y in 0..height {
+            for x in 0..width {
+                let bit = image.data[y * width.div_ceil(8) + x / 8] >> (7 - x % 8) & 1;
+                let v = bit * 255;
+                let base = (y * width + x) * 4;
+                assert_eq!(decoded.data[base..base + 4], [v, v, v, 255]);
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_empty() {
+        let image = GrayImage {
+            data: Vec::new(),
+            width: 0,
+            height: 5,
+        };
+        assert!(matches!(
+            encode_png_gray(&image, 6),
+            Err(EncodeError::Size(_))
+        ));
+    }
+}
@@ -0,0 +1,400 @@
+use super::{CLEN_ORDER, DIST_BASE, DIST_EXTRA, LEN_BASE, LEN_EXTRA};
+use alloc::vec::Vec;
+
+const WINDOW: usize = 1 << 15;
+const HASH_BITS: u32 = 15;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+// tokens per dynamic block
+const BLOCK_TOKENS: usize = 1 << 15;
+// hash chain links followed per position at each level
+const CHAIN: [usize; 10] = [0, 4, 8, 16, 32, 64, 128, 256, 1024, 4096];
+// a match of this length ends the chain search early
+const NICE: [usize; 10] = [0, 8, 16, 32, 64, 128, 128, 258, 258, 258];
+// matches are stored with this bit set, the length in bits 16-24 and the distance below
+const MATCH: u32 = 1 << 31;
+const LAZY: usize = 4;
+
+struct Bits<'a> {
+    out: &'a mut Vec<u8>,
+    acc: u64,
+    count: u32,
+}
+
+impl<'a> Bits<'a> {
+    fn write(&mut self, val: u32, n: u32) {
+        self.acc |= (val as u64) << self.count;
+        self.count += n;
+        while self.count >= 8 {
+            self.out.push(self.acc as u8);
+            self.acc >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    fn flush(&mut self) {
+        if self.count > 0 {
+            self.out.push(self.acc as u8);
+            self.acc = 0;
+            self.count = 0;
+        }
+    }
+}
+
+// Huffman code lengths limited to max_len bits
+fn lengths(freqs: &[u32], max_len: u8) -> Vec<u8> {
+    let mut freqs = freqs.to_vec();
+    loop {
+        let lens = depths(&freqs);
+        if lens.iter().all(|&len| len <= max_len) {
+            return lens;
+        }
+        // flattening the distribution shortens the longest codes
+        for f in freqs.iter_mut().filter(|f| **f > 0) {
+            *f = (*f + 1) >> 1;
+        }
+    }
+}
+
+fn depths(freqs: &[u32]) -> Vec<u8> {
+    let mut lens = vec![0; freqs.len()];
+    let mut syms: Vec<usize> = (0..freqs.len()).filter(|&i| freqs[i] > 0).collect();
+    if syms.len() < 2 {
+        // a lone symbol still needs a one bit code
+        for &sym in &syms {
+            lens[sym] = 1;
+        }
+        return lens;
+    }
+    syms.sort_by_key(|&i| freqs[i]);
+    // leaves in ascending weight, followed by internal nodes in creation order
+    let leaves = syms.len();
+    let mut weight: Vec<u64> = syms.iter().map(|&i| freqs[i] as u64).collect();
+    let mut parent = vec![0; leaves * 2 - 1];
+    let (mut leaf, mut node) = (0, leaves);
+    for _ in 0..leaves - 1 {
+        let mut pick = || {
+            if leaf < leaves && (node >= weight.len() || weight[leaf] <= weight[node]) {
+                leaf += 1;
+                leaf - 1
+            } else {
+                node += 1;
+                node - 1
+            }
+        };
+        let (x, y) = (pick(), pick());
+        parent[x] = weight.len();
+        parent[y] = weight.len();
+        weight.push(weight[x] + weight[y]);
+    }
+    // parents come after their children, so one backwards pass gives every depth
+    let mut depth = vec![0u8; weight.len()];
+    for i in (0..weight.len() - 1).rev() {
+        depth[i] = depth[parent[i]] + 1;
+    }
+    for (i, &sym) in syms.iter().enumerate() {
+        lens[sym] = depth[i];
+    }
+    lens
+}
+
+// canonical codes, bit reversed for the LSB-first stream
+fn codes(lens: &[u8]) -> Vec<u16> {
+    let mut counts = [0u16; 16];
+    for &len in lens {
+        counts[len as usize] += 1;
+    }
+    counts[0] = 0;
+    let mut next = [0u16; 16];
+    for len in 1..16 {
+        next[len] = (next[len - 1] + counts[len - 1]) << 1;
+    }
+    lens.iter()
+        .map(|&len| {
+            if len == 0 {
+                return 0;
+            }
+            let code = next[len as usize];
+            next[len as usize] += 1;
+            code.reverse_bits() >> (16 - len)
+        })
+        .collect()
+}
+
+fn len_symbol(len: usize) -> usize {
+    LEN_BASE.partition_point(|&base| base as usize <= len) - 1
+}
+
+fn dist_symbol(dist: usize) -> usize {
+    DIST_BASE.partition_point(|&base| base as usize <= dist) - 1
+}
+
+// run-length codes for the concatenated code lengths, as symbol and extra bits
+fn rle(lens: &[u8]) -> Vec<(u8, u8)> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < lens.len() {
+        let len = lens[i];
+        let mut run = 1;
+        while i + run < lens.len() && lens[i + run] == len {
+            run += 1;
+        }
+        i += run;
+        if len == 0 {
+            while run >= 11 {
+                let n = run.min(138);
+                out.push((18, (n - 11) as u8));
+                run -= n;
+            }
+            if run >= 3 {
+                out.push((17, (run - 3) as u8));
+                run = 0;
+            }
+        } else {
+            out.push((len, 0));
+            run -= 1;
+            while run >= 3 {
+                let n = run.min(6);
+                out.push((16, (n - 3) as u8));
+                run -= n;
+            }
+        }
+        for _ in 0..run {
+            out.push((len, 0));
+        }
+    }
+    out
+}
+
+// raw bytes as stored blocks of up to 64 KiB
+fn stored(bits: &mut Bits, raw: &[u8], last: bool) {
+    let mut chunks = raw.chunks(0xFFFF).peekable();
+    if chunks.peek().is_none() {
+        bits.write(last as u32, 3);
+        bits.flush();
+        bits.out.extend_from_slice(&[0, 0, 0xFF, 0xFF]);
+    }
+    while let Some(chunk) = chunks.next() {
+        bits.write((last && chunks.peek().is_none()) as u32, 3);
+        bits.flush();
+        let len = chunk.len() as u16;
+        bits.out.extend_from_slice(&len.to_le_bytes());
+        bits.out.extend_from_slice(&(!len).to_le_bytes());
+        bits.out.extend_from_slice(chunk);
+    }
+}
+
+// a dynamic Huffman block for tokens covering raw, or stored blocks if that is smaller
+fn block(bits: &mut Bits, tokens: &[u32], raw: &[u8], last: bool) {
+    let mut lit_freqs = [0u32; 286];
+    let mut dist_freqs = [0u32; 30];
+    lit_freqs[256] = 1;
+    for &token in tokens {
+        if token & MATCH != 0 {
+            lit_freqs[257 + len_symbol((token >> 16 & 0x1FF) as usize)] += 1;
+            dist_freqs[dist_symbol((token & 0xFFFF) as usize)] += 1;
+        } else {
+            lit_freqs[token as usize] += 1;
+        }
+    }
+    let lit_lens = lengths(&lit_freqs, 15);
+    let mut dist_lens = lengths(&dist_freqs, 15);
+    if dist_lens.iter().all(|&len| len == 0) {
+        dist_lens[0] = 1;
+    }
+    let hlit = 257.max(lit_lens.iter().rposition(|&len| len != 0).unwrap() + 1);
+    let hdist = dist_lens.iter().rposition(|&len| len != 0).unwrap() + 1;
+    let mut all = lit_lens[..hlit].to_vec();
+    all.extend_from_slice(&dist_lens[..hdist]);
+    let runs = rle(&all);
+    let mut clen_freqs = [0u32; 19];
+    for &(sym, _) in &runs {
+        clen_freqs[sym as usize] += 1;
+    }
+    let clen_lens = lengths(&clen_freqs, 7);
+    let hclen = 4.max(CLEN_ORDER.iter().rposition(|&i| clen_lens[i] != 0).unwrap() + 1);
+
+    let extra = |sym: usize| match sym {
+        16 => 2,
+        17 => 3,
+        18 => 7,
+        _ => 0,
+    };
+    let mut cost = 17 + 3 * hclen;
+    for &(sym, _) in &runs {
+        cost += clen_lens[sym as usize] as usize + extra(sym as usize);
+    }
+    for (sym, &freq) in lit_freqs.iter().enumerate() {
+        let extra = if sym > 256 { LEN_EXTRA[sym - 257] } else { 0 };
+        cost += freq as usize * (lit_lens[sym] + extra) as usize;
+    }
+    for (sym, &freq) in dist_freqs.iter().enumerate() {
+        cost += freq as usize * (dist_lens[sym] + DIST_EXTRA[sym]) as usize;
+    }
+    if cost >= (raw.len() + 5 * raw.len().div_ceil(0xFFFF)) * 8 + 7 {
+        stored(bits, raw, last);
+        return;
+    }
+
+    bits.write(last as u32, 1);
+    bits.write(2, 2);
+    bits.write((hlit - 257) as u32, 5);
+    bits.write((hdist - 1) as u32, 5);
+    bits.write((hclen - 4) as u32, 4);
+    for &i in &CLEN_ORDER[..hclen] {
+        bits.write(clen_lens[i] as u32, 3);
+    }
+    let clen_codes = codes(&clen_lens);
+    for &(sym, val) in &runs {
+        let sym = sym as usize;
+        bits.write(clen_codes[sym] as u32, clen_lens[sym] as u32);
+        bits.write(val as u32, extra(sym) as u32);
+    }
+
+    let lit_codes = codes(&lit_lens);
+    let dist_codes = codes(&dist_lens);
+    for &token in tokens {
+        if token & MATCH != 0 {
+            let len = (token >> 16 & 0x1FF) as usize;
+            let dist = (token & 0xFFFF) as usize;
+            let sym = len_symbol(len);
+            bits.write(lit_codes[257 + sym] as u32, lit_lens[257 + sym] as u32);
+            bits.write((len - LEN_BASE[sym] as usize) as u32, LEN_EXTRA[sym] as u32);
+            let sym = dist_symbol(dist);
+            bits.write(dist_codes[sym] as u32, dist_lens[sym] as u32);
+            bits.write(
+                (dist - DIST_BASE[sym] as usize) as u32,
+                DIST_EXTRA[sym] as u32,
+            );
+        } else {
+            let sym = token as usize;
+            bits.write(lit_codes[sym] as u32, lit_lens[sym] as u32);
+        }
+    }
+    bits.write(lit_codes[256] as u32, lit_lens[256] as u32);
+}
+
+fn hash(data: &[u8], i: usize) -> usize {
+    let v = (data[i] as u32) << 16 | (data[i + 1] as u32) << 8 | data[i + 2] as u32;
+    (v.wrapping_mul(0x9E3779B1) >> (32 - HASH_BITS)) as usize
+}
+
+struct Matcher<'a> {
+    data: &'a [u8],
+    // most recent position + 1 with each hash, and the previous one for each window slot
+    head: Vec<u32>,
+    prev: Vec<u32>,
+    chain: usize,
+    nice: usize,
+}
+
+impl<'a> Matcher<'a> {
+    fn insert(&mut self, i: usize) {
+        if i + MIN_MATCH <= self.data.len() {
+            let h = hash(self.data, i);
+            self.prev[i & (WINDOW - 1)] = self.head[h];
+            self.head[h] = i as u32 + 1;
+        }
+    }
+
+    // longest earlier match for position i, as length and distance
+    fn find(&self, i: usize) -> (usize, usize) {
+        let data = self.data;
+        let (mut best_len, mut best_dist) = (0, 0);
+        if i + MIN_MATCH > data.len() {
+            return (0, 0);
+        }
+        let max_len = MAX_MATCH.min(data.len() - i);
+        let mut cand = self.head[hash(data, i)] as usize;
+        let mut chain = self.chain;
+        while cand > 0 && chain > 0 {
+            let j = cand - 1;
+            if i - j > WINDOW - MAX_MATCH {
+                break;
+            }
+            let probe = best_len.min(max_len - 1);
+            if data[j + probe] == data[i + probe] {
+                let len = data[j..j + max_len]
+                    .iter()
+                    .zip(&data[i..i + max_len])
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                if len > best_len {
+                    best_len = len;
+                    best_dist = i - j;
+                    if len >= self.nice {
+                        break;
+                    }
+                }
+            }
+            let next = self.prev[j & (WINDOW - 1)] as usize;
+            if next >= cand {
+                break;
+            }
+            cand = next;
+            chain -= 1;
+        }
+        (best_len, best_dist)
+    }
+}
+
+// raw deflate stream of data onto out
+pub fn deflate(data: &[u8], level: u8, out: &mut Vec<u8>) {
+    let mut bits = Bits {
+        out,
+        acc: 0,
+        count: 0,
+    };
+    let level = level.min(9) as usize;
+    if level == 0 || data.is_empty() {
+        stored(&mut bits, data, true);
+        return;
+    }
+
+    let mut matcher = Matcher {
+        data,
+        head: vec![0; 1 << HASH_BITS],
+        prev: vec![0; WINDOW],
+        chain: CHAIN[level],
+        nice: NICE[level],
+    };
+    // levels from LAZY up defer a match when the next position has a longer one
+    let lazy = level >= LAZY;
+    let mut tokens = Vec::with_capacity(BLOCK_TOKENS);
+    let mut block_start = 0;
+    let mut i = 0;
+    let mut pending = matcher.find(0);
+    while i < data.len() {
+        let (len, dist) = pending;
+        matcher.insert(i);
+        if len >= MIN_MATCH {
+            let next = if lazy && len < matcher.nice {
+                matcher.find(i + 1)
+            } else {
+                (0, 0)
+            };
+            if next.0 > len {
+                tokens.push(data[i] as u32);
+                i += 1;
+                pending = next;
+            } else {
+                tokens.push(MATCH | (len as u32) << 16 | dist as u32);
+                for k in i + 1..i + len {
+                    matcher.insert(k);
+                }
+                i += len;
+                pending = matcher.find(i);
+            }
+        } else {
+            tokens.push(data[i] as u32);
+            i += 1;
+            pending = matcher.find(i);
+        }
+        if tokens.len() == BLOCK_TOKENS || i == data.len() {
+            block(&mut bits, &tokens, &data[block_start..i], i == data.len());
+            tokens.clear();
+            block_start = i;
+        }
+    }
+    bits.flush();
+}
@@ -0,0 +1,250 @@
+use super::{DecodeError, CLEN_ORDER, DIST_BASE, DIST_EXTRA, LEN_BASE, LEN_EXTRA};
+use alloc::vec::Vec;
+
+const TOO_LONG: DecodeError = DecodeError::Invalid("more data than expected");
+
+// LSB-first bit reader that pads the input with zeros, failing only once padding is consumed
+struct Bits<'a> {
+    data: &'a [u8],
+    pos: usize,
+    acc: u64,
+    count: u32,
+    padding: u32,
+}
+
+impl<'a> Bits<'a> {
+    fn refill(&mut self) {
+        while self.count <= 56 {
+            let byte = match self.data.get(self.pos) {
+                Some(&byte) => byte,
+                None => {
+                    self.padding += 8;
+                    0
+                }
+            };
+            self.pos += 1;
+            self.acc |= (byte as u64) << self.count;
+            self.count += 8;
+        }
+    }
+
+    fn consume(&mut self, n: u32) -> Result<(), DecodeError> {
+        self.acc >>= n;
+        self.count -= n;
+        if self.count < self.padding {
+            Err(DecodeError::Truncated)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn bits(&mut self, n: u32) -> Result<u32, DecodeError> {
+        self.refill();
+        let val = (self.acc & ((1 << n) - 1)) as u32;
+        self.consume(n)?;
+        Ok(val)
+    }
+
+    // drops bits up to the next byte boundary
+    fn align(&mut self) {
+        let rem = self.count & 7;
+        self.acc >>= rem;
+        self.count -= rem;
+    }
+
+    // bytes read from the input so far, once aligned
+    fn used(&self) -> usize {
+        self.pos - (self.count / 8) as usize
+    }
+}
+
+struct Huffman {
+    // symbol << 4 | code length, indexed by the next bits of input
+    table: Vec<u16>,
+    bits: u32,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Huffman, DecodeError> {
+        let mut counts = [0u16; 16];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut left = 1i32;
+        let mut next = [0u16; 16];
+        for len in 1..16 {
+            left = (left << 1) - counts[len] as i32;
+            if left < 0 {
+                return Err(DecodeError::Invalid("oversubscribed Huffman code"));
+            }
+            next[len] = (next[len - 1] + counts[len - 1]) << 1;
+        }
+        let bits = lengths.iter().copied().max().unwrap_or(0).max(1) as u32;
+        let mut table = vec![0; 1 << bits];
+        for (sym, &len) in lengths.iter().enumerate() {
+            if len == 0 {
+                continue;
+            }
+            let code = next[len as usize];
+            next[len as usize] += 1;
+            let rev = (code.reverse_bits() >> (16 - len)) as usize;
+            let entry = (sym as u16) << 4 | len as u16;
+            for i in (rev..table.len()).step_by(1 << len) {
+                table[i] = entry;
+            }
+        }
+        Ok(Huffman { table, bits })
+    }
+
+    fn decode(&self, bits: &mut Bits) -> Result<u16, DecodeError> {
+        bits.refill();
+        let entry = self.table[(bits.acc & ((1 << self.bits) - 1)) as usize];
+        let len = (entry & 15) as u32;
+        if len == 0 {
+            return Err(DecodeError::Invalid("incomplete Huffman code"));
+        }
+        bits.consume(len)?;
+        Ok(entry >> 4)
+    }
+}
+
+fn fixed() -> (Huffman, Huffman) {
+    let mut lengths = [0; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+    (
+        Huffman::new(&lengths).unwrap(),
+        Huffman::new(&[5; 30]).unwrap(),
+    )
+}
+
+fn dynamic(bits: &mut Bits) -> Result<(Huffman, Huffman), DecodeError> {
+    let hlit = bits.bits(5)? as usize + 257;
+    let hdist = bits.bits(5)? as usize + 1;
+    let hclen = bits.bits(4)? as usize + 4;
+    let mut clens = [0; 19];
+    for &i in &CLEN_ORDER[..hclen] {
+        clens[i] = bits.bits(3)? as u8;
+    }
+    let clen = Huffman::new(&clens)?;
+    let mut lengths = [0; 320];
+    let mut i = 0;
+    while i < hlit + hdist {
+        let sym = clen.decode(bits)?;
+        let (val, repeat) = match sym {
+            0..=15 => (sym as u8, 1),
+            16 => {
+                if i == 0 {
+                    return Err(DecodeError::Invalid("repeat with no previous length"));
+                }
+                (lengths[i - 1], 3 + bits.bits(2)? as usize)
+            }
+            17 => (0, 3 + bits.bits(3)? as usize),
+            _ => (0, 11 + bits.bits(7)? as usize),
+        };
+        if i + repeat > hlit + hdist {
+            return Err(DecodeError::Invalid("code lengths overflow"));
+        }
+        lengths[i..i + repeat].fill(val);
+        i += repeat;
+    }
+    if lengths[256] == 0 {
+        return Err(DecodeError::Invalid("missing end of block code"));
+    }
+    Ok((
+        Huffman::new(&lengths[..hlit])?,
+        Huffman::new(&lengths[hlit..hlit + hdist])?,
+    ))
+}
+
+fn codes(
+    bits: &mut Bits,
+    out: &mut Vec<u8>,
+    lit: &Huffman,
+    dist: &Huffman,
+    limit: usize,
+) -> Result<(), DecodeError> {
+    loop {
+        let sym = lit.decode(bits)? as usize;
+        if sym < 256 {
+            if out.len() >= limit {
+                return Err(TOO_LONG);
+            }
+            out.push(sym as u8);
+            continue;
+        }
+        if sym == 256 {
+            return Ok(());
+        }
+        let sym = sym - 257;
+        if sym >= 29 {
+            return Err(DecodeError::Invalid("length symbol"));
+        }
+        let len = LEN_BASE[sym] as usize + bits.bits(LEN_EXTRA[sym] as u32)? as usize;
+        let sym = dist.decode(bits)? as usize;
+        if sym >= 30 {
+            return Err(DecodeError::Invalid("distance symbol"));
+        }
+        let d = DIST_BASE[sym] as usize + bits.bits(DIST_EXTRA[sym] as u32)? as usize;
+        if d > out.len() {
+            return Err(DecodeError::Invalid("distance too far back"));
+        }
+        if len > limit - out.len() {
+            return Err(TOO_LONG);
+        }
+        let start = out.len() - d;
+        if d >= len {
+            out.extend_from_within(start..start + len);
+        } else {
+            for i in 0..len {
+                out.push(out[start + i]);
+            }
+        }
+    }
+}
+
+// decodes a raw deflate stream onto out, returning how many input bytes it took; out may not
+// grow past limit bytes
+pub fn inflate(data: &[u8], out: &mut Vec<u8>, limit: usize) -> Result<usize, DecodeError> {
+    let mut bits = Bits {
+        data,
+        pos: 0,
+        acc: 0,
+        count: 0,
+        padding: 0,
+    };
+    loop {
+        let last = bits.bits(1)? == 1;
+        match bits.bits(2)? {
+            0 => {
+                bits.align();
+                let len = bits.bits(16)?;
+                if bits.bits(16)? != !len & 0xFFFF {
+                    return Err(DecodeError::Invalid("stored block length"));
+                }
+                if len as usize > limit - out.len() {
+                    return Err(TOO_LONG);
+                }
+                for _ in 0..len {
+                    out.push(bits.bits(8)? as u8);
+                }
+            }
+            1 => {
+                let (lit, dist) = fixed();
+                codes(&mut bits, out, &lit, &dist, limit)?;
+            }
+            2 => {
+                let (lit, dist) = dynamic(&mut bits)?;
+                codes(&mut bits, out, &lit, &dist, limit)?;
+            }
+            _ => return Err(DecodeError::Invalid("block type")),
+        }
+        if last {
+            bits.align();
+            return Ok(bits.used());
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use super::DecodeError;
+use alloc::vec::Vec;
+
+mod deflate;
+mod inflate;
+pub use deflate::deflate;
+pub use inflate::inflate;
+
+// base value and extra bits of each length symbol, starting at 257
+const LEN_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LEN_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+// order in which code length code lengths are stored
+const CLEN_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    // largest run before the sums can overflow
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+// zlib stream at the given level, 0 (stored) to 9 (slowest)
+pub fn compress(data: &[u8], level: u8) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    let level = level.min(9);
+    // FLEVEL hint, then FCHECK so the header is a multiple of 31
+    out[1] = match level {
+        0 | 1 => 0,
+        2..=5 => 1,
+        6 => 2,
+        _ => 3,
+    } << 6;
+    out[1] += (31 - ((out[0] as u16) << 8 | out[1] as u16) % 31) as u8 % 31;
+    deflate(data, level, &mut out);
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>, DecodeError> {
+    decompress_limited(data, usize::MAX)
+}
+
+// decompress, failing once the output would grow past limit bytes
+pub fn decompress_limited(data: &[u8], limit: usize) -> Result<Vec<u8>, DecodeError> {
+    if data.len() < 2 {
+        return Err(DecodeError::Truncated);
+    }
+    let (cmf, flg) = (data[0], data[1]);
+    if cmf & 15 != 8 || cmf >> 4 > 7 || !((cmf as u16) << 8 | flg as u16).is_multiple_of(31) {
+        return Err(DecodeError::Invalid("zlib header"));
+    }
+    if flg & 0x20 != 0 {
+        return Err(DecodeError::Unsupported("zlib preset dictionary"));
+    }
+    let mut out = Vec::new();
+    let used = inflate(&data[2..], &mut out, limit)?;
+    let check = data.get(2 + used..6 + used).ok_or(DecodeError::Truncated)?;
+    if u32::from_be_bytes([check[0], check[1], check[2], check[3]]) != adler32(&out) {
+        return Err(DecodeError::Invalid("zlib checksum"));
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compress, decompress};
+    use alloc::vec::Vec;
+
+    // noise broken up by runs, longer than the window and a stored block
+    fn sample() -> Vec<u8> {
+        let mut state = 1u32;
+        let mut out = Vec::new();
+        while out.len() < 100_000 {
+            state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+            let byte = (state >> 24) as u8;
+            let run = if byte < 32 { byte as usize * 8 } else { 1 };
+            out.extend((0..run).map(|i| byte.wrapping_add((i / 16) as u8)));
+        }
+        out
+    }
+
+    #[test]
+    fn round_trips() {
+        let data = sample();
+        for level in 0..=9 {
+            for len in [0, 1, 258, data.len()] {
+                let packed = compress(&data[..len], level);
+                assert_eq!(
+                    decompress(&packed).unwrap(),
+                    &data[..len],
+                    "level {}",
+                    level
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,43 @@
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use super::{image::Workspace, scale_quad, sort_quad, DetectOptions, RGBAImage, ScoredQuad};
+
+// find_document for a stream of frames; its buffers are kept between frames, so once they have
+// grown to the frame size, serial builds detect without allocating
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub struct Detector {
+    options: DetectOptions,
+    workspace: Workspace,
+    // frames handed over from JS are copied here
+    #[cfg(feature = "wasm")]
+    pub(crate) frame: RGBAImage,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl Detector {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new(options: DetectOptions) -> Detector {
+        Detector {
+            options: options.clamped(),
+            workspace: Workspace::default(),
+            #[cfg(feature = "wasm")]
+            frame: RGBAImage::default(),
+        }
+    }
+}
+
+impl Detector {
+    pub fn find(&mut self, rgba: &RGBAImage) -> Option<ScoredQuad> {
+        let by = super::detection_scale(rgba, &self.options);
+        self.workspace.prepare(rgba, by);
+        let doc = *self.workspace.documents(&self.options).first()?;
+        let quad = scale_quad(sort_quad(doc.quad), by);
+        Some(ScoredQuad {
+            quad: self
+                .workspace
+                .refine(rgba, quad, self.options.refine_band * by),
+            ..doc
+        })
+    }
+}
@@ -0,0 +1,97 @@
+// work over an image is split into bands of whole rows; with the parallel feature the bands run
+// on rayon's pool, otherwise the whole image is one band on the calling thread
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use alloc::vec::Vec;
+
+// enough work per band that scheduling it costs next to nothing
+const BAND_WORK: usize = 1 << 16;
+
+// row buffers a band works in; serial builds use the caller's, so they can be kept between calls
+#[derive(Default)]
+pub struct Scratch {
+    pub acc: Vec<f32>,
+    pub row: Vec<f32>,
+}
+
+// rows per band for rows that each take about cost units of work (roughly pixels touched)
+pub fn band_rows(cost: usize) -> usize {
+    (BAND_WORK / cost.max(1)).max(1)
+}
+
+// bands to split into when every band needs its own large scratch buffer
+pub fn threads() -> usize {
+    #[cfg(feature = "parallel")]
+    {
+        rayon::current_num_threads()
+    }
+    #[cfg(not(feature = "parallel"))]
+    {
+        1
+    }
+}
+
+// calls f with the index of the first row of each band, the band's rows of data, in which every
+// row is row_len items long, and scratch space for the band
+pub fn rows<T: Send, S: Default + Send>(
+    data: &mut [T],
+    row_len: usize,
+    band_rows: usize,
+    scratch: &mut S,
+    f: impl Fn(usize, &mut [T], &mut S) + Sync + Send,
+) {
+    #[cfg(feature = "parallel")]
+    {
+        let _ = scratch;
+        let band = (row_len * band_rows).max(1);
+        data.par_chunks_mut(band)
+            .enumerate()
+            .for_each_init(S::default, |scratch, (i, rows)| {
+                f(i * band_rows, rows, scratch)
+            });
+    }
+    #[cfg(not(feature = "parallel"))]
+    {
+        let _ = (row_len, band_rows);
+        f(0, data, scratch);
+    }
+}
+
+// rows, but band i works in state[i], which outlives the call; state needs an entry per band
+pub fn rows_with<T: Send, U: Send>(
+    data: &mut [T],
+    row_len: usize,
+    band_rows: usize,
+    state: &mut [U],
+    f: impl Fn(usize, &mut [T], &mut U) + Sync + Send,
+) {
+    let band = (row_len * band_rows).max(1);
+    #[cfg(feature = "parallel")]
+    {
+        data.par_chunks_mut(band)
+            .zip(state.par_iter_mut())
+            .enumerate()
+            .for_each(|(i, (rows, state))| f(i * band_rows, rows, state));
+    }
+    #[cfg(not(feature = "parallel"))]
+    {
+        data.chunks_mut(band)
+            .zip(state.iter_mut())
+            .enumerate()
+            .for_each(|(i, (rows, state))| f(i * band_rows, rows, state));
+    }
+}
+
+// f over 0..count, for work that isn't laid out in rows
+pub fn map<R: Send>(count: usize, f: impl Fn(usize) -> R + Sync + Send) -> Vec<R> {
+    #[cfg(feature = "parallel")]
+    {
+        (0..count).into_par_iter().map(f).collect()
+    }
+    #[cfg(not(feature = "parallel"))]
+    {
+        (0..count).map(f).collect()
+    }
+}
@@ -0,0 +1,188 @@
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use super::{BitImage, GrayImage, Image};
+use alloc::vec::Vec;
+use libm::sqrtf;
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum Threshold {
+    // mean * (1 + k * (deviation / 0.5 - 1))
+    #[default]
+    Sauvola,
+    // Sauvola normalized by the image's darkest pixel and largest deviation, for low contrast
+    Wolf,
+    // mean * (1 - k), ignoring deviation
+    Bradley,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Copy)]
+pub struct BinarizeOptions {
+    pub method: Threshold,
+    // side of the local window relative to the shorter side of the page
+    pub window: f32,
+    // sensitivity of the method; 0 for its usual value
+    pub k: f32,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl BinarizeOptions {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new() -> BinarizeOptions {
+        BinarizeOptions {
+            method: Threshold::Sauvola,
+            window: 0.04,
+            k: 0.0,
+        }
+    }
+}
+
+impl Default for BinarizeOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// local mean and standard deviation from integral images of an 8-bit copy
+struct Windows {
+    width: usize,
+    height: usize,
+    radius: usize,
+    // sums wrap around, which box differences undo as long as one window fits in 32 bits
+    sum: Vec<u32>,
+    sq: Vec<u64>,
+}
+
+impl Windows {
+    fn new(gray: &GrayImage, radius: usize) -> Windows {
+        let (width, height) = (gray.width, gray.height);
+        let stride = width + 1;
+        let mut sum = vec![0u32; stride * (height + 1)];
+        let mut sq = vec![0u64; stride * (height + 1)];
+        for y in 0..height {
+            let (mut row_sum, mut row_sq) = (0u32, 0u64);
+            for x in 0..width {
+                let v = gray.data[y * width + x] as u32;
+                row_sum = row_sum.wrapping_add(v);
+                row_sq += (v * v) as u64;
+                let i = (y + 1) * stride + x + 1;
+                sum[i] = sum[i - stride].wrapping_add(row_sum);
+                sq[i] = sq[i - stride] + row_sq;
+            }
+        }
+        Windows {
+            width,
+            height,
+            radius,
+            sum,
+            sq,
+        }
+    }
+
+    // mean and deviation of the window around (x, y), scaled to 0-1
+    fn stats(&self, x: usize, y: usize) -> (f32, f32) {
+        let stride = self.width + 1;
+        let x0 = x.saturating_sub(self.radius);
+        let y0 = y.saturating_sub(self.radius);
+        let x1 = (x + self.radius + 1).min(self.width);
+        let y1 = (y + self.radius + 1).min(self.height);
+        let (a, b, c, d) = (
+            y0 * stride + x0,
+            y0 * stride + x1,
+            y1 * stride + x0,
+            y1 * stride + x1,
+        );
+        let n = ((x1 - x0) * (y1 - y0)) as f32;
+        let sum = self.sum[d]
+            .wrapping_sub(self.sum[b])
+            .wrapping_sub(self.sum[c])
+            .wrapping_add(self.sum[a]) as f32;
+        let sq = (self.sq[d] + self.sq[a] - self.sq[b] - self.sq[c]) as f32;
+        let mean = sum / n;
+        let var = (sq / n - mean * mean).max(0.0);
+        (mean / 255.0, sqrtf(var) / 255.0)
+    }
+}
+
+// true for each pixel that is ink
+fn ink(source: &Image, options: &BinarizeOptions) -> Vec<bool> {
+    let gray = source.to_gray8();
+    let side = source.width.min(source.height) as f32;
+    let radius = ((side * options.window) as usize / 2).max(1);
+    let windows = Windows::new(&gray, radius);
+    let (width, height) = (source.width, source.height);
+    let k = if options.k > 0.0 {
+        options.k
+    } else {
+        match options.method {
+            Threshold::Sauvola => 0.34,
+            Threshold::Wolf => 0.5,
+            Threshold::Bradley => 0.15,
+        }
+    };
+    let (min, max_dev) = match options.method {
+        Threshold::Wolf => {
+            let min = gray.data.iter().copied().min().unwrap_or(0) as f32 / 255.0;
+            let mut max_dev = 0.0f32;
+            for y in 0..height {
+                for x in 0..width {
+                    max_dev = max_dev.max(windows.stats(x, y).1);
+                }
+            }
+            (min, max_dev.max(f32::EPSILON))
+        }
+        _ => (0.0, 0.0),
+    };
+    let mut out = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            let (mean, dev) = windows.stats(x, y);
+            let threshold = match options.method {
+                Threshold::Sauvola => mean * (1.0 + k * (dev / 0.5 - 1.0)),
+                Threshold::Wolf => mean - k * (1.0 - dev / max_dev) * (mean - min),
+                Threshold::Bradley => mean * (1.0 - k),
+            };
+            out.push((gray.data[y * width + x] as f32 / 255.0) <= threshold);
+        }
+    }
+    out
+}
+
+// ink becomes 0 and paper 255
+pub fn binarize(source: &Image, options: &BinarizeOptions) -> GrayImage {
+    GrayImage {
+        data: ink(source, options)
+            .into_iter()
+            .map(|ink| if ink { 0 } else { 255 })
+            .collect(),
+        width: source.width,
+        height: source.height,
+    }
+}
+
+pub fn binarize_bits(source: &Image, options: &BinarizeOptions) -> BitImage {
+    if source.width == 0 || source.height == 0 {
+        return BitImage {
+            data: Vec::new(),
+            width: source.width,
+            height: source.height,
+        };
+    }
+    let ink = ink(source, options);
+    let stride = source.width.div_ceil(8);
+    let mut data = vec![0u8; stride * source.height];
+    for (y, row) in ink.chunks_exact(source.width).enumerate() {
+        for (x, &ink) in row.iter().enumerate() {
+            if !ink {
+                data[y * stride + (x >> 3)] |= 0x80 >> (x & 7);
+            }
+        }
+    }
+    BitImage {
+        data,
+        width: source.width,
+        height: source.height,
+    }
+}
@@ -0,0 +1,118 @@
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use super::RGBAImage;
+use libm::powf;
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Copy)]
+pub struct ColorOptions {
+    // fraction of the brightest pixels averaged to estimate the paper white
+    pub paper: f32,
+    // fractions of pixels clipped to black and to white by the contrast stretch
+    pub black: f32,
+    pub white: f32,
+    // exponent applied after the stretch; above 1 darkens midtones and thin ink
+    pub gamma: f32,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl ColorOptions {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new() -> ColorOptions {
+        ColorOptions {
+            paper: 0.1,
+            black: 0.01,
+            white: 0.05,
+            gamma: 1.0,
+        }
+    }
+}
+
+impl Default for ColorOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[inline]
+fn luma(px: &[u8]) -> usize {
+    (px[0] as usize * 299 + px[1] as usize * 587 + px[2] as usize * 114 + 500) / 1000
+}
+
+// lowest value with more than the given fraction of the histogram's total at or below it
+fn percentile(histogram: &[usize; 256], total: usize, fraction: f32) -> usize {
+    let target = (total as f32 * fraction.clamp(0.0, 1.0)) as usize;
+    let mut seen = 0;
+    for (v, &count) in histogram.iter().enumerate() {
+        seen += count;
+        if seen > target {
+            return v;
+        }
+    }
+    255
+}
+
+// white balance from the paper, then a shared contrast stretch and gamma; transparent pixels are ignored
+pub fn correct_color(source: &RGBAImage, options: &ColorOptions) -> RGBAImage {
+    let opaque = || source.data.chunks_exact(4).filter(|px| px[3] != 0);
+    let mut histogram = [0usize; 256];
+    for px in opaque() {
+        histogram[luma(px)] += 1;
+    }
+    let total: usize = histogram.iter().sum();
+    if total == 0 {
+        return RGBAImage {
+            data: source.data.clone(),
+            width: source.width,
+            height: source.height,
+        };
+    }
+
+    // the paper is the brightest large region, so its color is the light's color
+    let bright = percentile(&histogram, total, 1.0 - options.paper);
+    let mut sums = [0u64; 3];
+    let mut count = 0u64;
+    for px in opaque().filter(|px| luma(px) >= bright) {
+        for c in 0..3 {
+            sums[c] += px[c] as u64;
+        }
+        count += 1;
+    }
+    let paper = sums.map(|s| (s as f32 / count as f32).max(1.0));
+    let brightest = paper[0].max(paper[1]).max(paper[2]);
+    let gains = paper.map(|p| brightest / p);
+    let balance = |px: &[u8]| [0, 1, 2].map(|c| (px[c] as f32 * gains[c] + 0.5).min(255.0) as u8);
+
+    let mut balanced = [0usize; 256];
+    for px in opaque() {
+        balanced[luma(&balance(px))] += 1;
+    }
+    let lo = percentile(&balanced, total, options.black) as f32;
+    let hi = (percentile(&balanced, total, 1.0 - options.white) as f32).max(lo + 1.0);
+    let gamma = if options.gamma > 0.0 {
+        options.gamma
+    } else {
+        1.0
+    };
+    let mut curve = [0u8; 256];
+    for (v, out) in curve.iter_mut().enumerate() {
+        let t = ((v as f32 - lo) / (hi - lo)).clamp(0.0, 1.0);
+        *out = (powf(t, gamma) * 255.0 + 0.5) as u8;
+    }
+
+    let mut data = source.data.clone();
+    for px in data.chunks_exact_mut(4) {
+        if px[3] != 0 {
+            let balanced = balance(px);
+            for c in 0..3 {
+                px[c] = curve[balanced[c] as usize];
+            }
+        }
+    }
+    RGBAImage {
+        data,
+        width: source.width,
+        height: source.height,
+    }
+}
@@ -0,0 +1,103 @@
+use super::{Point, Quad};
+use libm::sqrtf;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+// elongation past which an unmatched page is taken to be a receipt
+const RECEIPT_RATIO: f32 = 2.2;
+// plausible focal lengths, relative to the longer side of the frame
+const MIN_FOCAL: f32 = 0.3;
+const MAX_FOCAL: f32 = 5.0;
+// typical phone main camera (about 26mm equivalent), used when the focal length can't be recovered
+const DEFAULT_FOCAL: f32 = 0.75;
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PaperSize {
+    A4,
+    Letter,
+    Legal,
+    Id1,
+    // thermal roll paper, which has no fixed length
+    Receipt,
+}
+
+impl PaperSize {
+    // ratio of the long side to the short side
+    pub fn ratio(self) -> Option<f32> {
+        match self {
+            PaperSize::A4 => Some(297.0 / 210.0),
+            PaperSize::Letter => Some(11.0 / 8.5),
+            PaperSize::Legal => Some(14.0 / 8.5),
+            PaperSize::Id1 => Some(85.6 / 53.98),
+            PaperSize::Receipt => None,
+        }
+    }
+}
+
+type Vec3 = [f32; 3];
+
+fn cross(a: Vec3, b: Vec3) -> Vec3 {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn dot(a: Vec3, b: Vec3) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+// Zhang and He's rectangle aspect ratio recovery for a pinhole camera with the
+// principal point at the center of the frame; width / height of the real document
+// for the corner order used by perspective. Pass a focal length of 0 to estimate it.
+pub fn aspect_ratio(quad: Quad, width: usize, height: usize, focal_length: f32) -> f32 {
+    let cx = width as f32 * 0.5;
+    let cy = height as f32 * 0.5;
+    let m = |p: Point| [p.x - cx, p.y - cy, 1.0];
+    let (m1, m2, m3, m4) = (m(quad.b), m(quad.c), m(quad.a), m(quad.d));
+    let k2 = dot(cross(m1, m4), m3) / dot(cross(m2, m4), m3);
+    let k3 = dot(cross(m1, m4), m2) / dot(cross(m3, m4), m2);
+    let n2 = [k2 * m2[0] - m1[0], k2 * m2[1] - m1[1], k2 - 1.0];
+    let n3 = [k3 * m3[0] - m1[0], k3 * m3[1] - m1[1], k3 - 1.0];
+    let longest = width.max(height) as f32;
+    let mut f2 = if focal_length > 0.0 {
+        focal_length * focal_length
+    } else {
+        -(n2[0] * n3[0] + n2[1] * n3[1]) / (n2[2] * n3[2])
+    };
+    let plausible = (MIN_FOCAL * longest) * (MIN_FOCAL * longest)
+        ..=(MAX_FOCAL * longest) * (MAX_FOCAL * longest);
+    if !plausible.contains(&f2) {
+        // a pair of sides is (nearly) parallel in the image, which leaves the focal length unknown
+        f2 = (DEFAULT_FOCAL * longest) * (DEFAULT_FOCAL * longest);
+    }
+    sqrtf(
+        (n2[0] * n2[0] + n2[1] * n2[1] + f2 * n2[2] * n2[2])
+            / (n3[0] * n3[0] + n3[1] * n3[1] + f2 * n3[2] * n3[2]),
+    )
+}
+
+// snaps a width / height ratio to the closest known paper size within tolerance
+pub fn snap_aspect(ratio: f32, tolerance: f32) -> (f32, Option<PaperSize>) {
+    let long = ratio.max(1.0 / ratio);
+    let mut best: Option<(PaperSize, f32, f32)> = None;
+    for size in [
+        PaperSize::A4,
+        PaperSize::Letter,
+        PaperSize::Legal,
+        PaperSize::Id1,
+    ] {
+        let target = size.ratio().unwrap();
+        let err = (long / target - 1.0).abs();
+        if err <= tolerance && best.is_none_or(|(_, _, best_err)| err < best_err) {
+            best = Some((size, target, err));
+        }
+    }
+    match best {
+        Some((size, target, _)) => (if ratio >= 1.0 { target } else { 1.0 / target }, Some(size)),
+        None if long >= RECEIPT_RATIO => (ratio, Some(PaperSize::Receipt)),
+        None => (ratio, None),
+    }
+}
@@ -7,6 +7,18 @@ pub const GRADIENT_ERROR: u8 = 8;
 pub const GRADIENT_OFFSET: f32 = 3.0;
 pub const HOUGH_MATCH_RATIO: f32 = 1.0 / 40.0;
 pub const MAX_ANG_ERROR: u8 = (HOUGH_MATCH_RATIO * 256.0) as u8;
+pub const EDGE_THRESHOLD: f32 = 0.05;
+pub const MAX_LINES: usize = 20;
+pub const RESOLUTION: f32 = 360.0;
+// smallest resolution that leaves room for a document after the borders are dropped
+pub const MIN_RESOLUTION: f32 = 16.0;
+pub const MAX_RADIUS: f32 = 0.55;
+pub const DUPLICATE_DISTANCE: f32 = 0.05;
+pub const MIN_AREA: f32 = 0.2;
+pub const REFINE_BAND: f32 = 3.0;
+pub const MAX_OVERLAP: f32 = 0.1;
+pub const MIN_CONFIDENCE: f32 = 0.7;
+pub const MULTI_MIN_AREA: f32 = 0.01;
 
 pub const SIN: [f32; 256] = [
     6.123234e-17,
@@ -0,0 +1,174 @@
+use super::{
+    super::{Image, RGBAImage},
+    consts::ANGS_PER_RAD,
+    detect::{dominant_angle, gradient_votes},
+    perspective::perspective,
+    DetectOptions, Point, Quad, WarpOptions,
+};
+use core::f32::consts::PI;
+use libm::{ceilf, cosf, sinf};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Copy)]
+pub struct DeskewOptions {
+    // largest rotation in degrees that is corrected; larger skews are left alone
+    pub max_angle: f32,
+    // length of the shorter side the page is downscaled to before voting
+    pub resolution: f32,
+    // fraction of each side ignored so the page border doesn't outvote the content
+    pub margin: f32,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl DeskewOptions {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new() -> DeskewOptions {
+        DeskewOptions {
+            max_angle: 5.0,
+            resolution: 800.0,
+            margin: 0.05,
+        }
+    }
+}
+
+impl Default for DeskewOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// clockwise rotation of the content in degrees
+pub fn skew_angle(source: &RGBAImage, options: &DeskewOptions) -> f32 {
+    let side = source.width.min(source.height) as f32;
+    let gray = if side > options.resolution && options.resolution > 0.0 {
+        source.to_grayscale_downscaled(side / options.resolution)
+    } else {
+        source.to_grayscale()
+    };
+    let margin_x = (gray.width as f32 * options.margin) as usize;
+    let margin_y = (gray.height as f32 * options.margin) as usize;
+    let width = gray.width.saturating_sub(margin_x * 2);
+    let height = gray.height.saturating_sub(margin_y * 2);
+    if width < 3 || height < 3 {
+        return 0.0;
+    }
+    // on unblurred edges the 3x3 gradient directions snap toward the axes
+    let inner = Image {
+        data: (margin_y..margin_y + height)
+            .flat_map(|y| {
+                let row = y * gray.width + margin_x;
+                gray.data[row..row + width].iter().copied()
+            })
+            .collect(),
+        width,
+        height,
+    }
+    .gaussian();
+    // a narrow vote spread keeps the angle sharp; the accumulator is otherwise the detector's
+    let votes = gradient_votes(
+        &inner,
+        &DetectOptions {
+            gradient_error: 1,
+            ..DetectOptions::new()
+        },
+    );
+    let max_offset = ceilf(options.max_angle.max(0.0) * PI / 180.0 * ANGS_PER_RAD) as u8;
+    // Hough angles grow counterclockwise on screen
+    let angle = -dominant_angle(&votes, max_offset) / ANGS_PER_RAD * 180.0 / PI;
+    if angle.abs() > options.max_angle {
+        0.0
+    } else {
+        angle
+    }
+}
+
+// rotates by the given clockwise angle in degrees around the center, keeping the size
+pub fn rotate_by(source: &RGBAImage, degrees: f32, options: &WarpOptions) -> RGBAImage {
+    let (width, height) = (source.width, source.height);
+    let (wf, hf) = (width as f32, height as f32);
+    let rad = degrees * PI / 180.0;
+    let (sin, cos) = (sinf(rad), cosf(rad));
+    // output pixels are sampled from the source rotated the other way
+    let corner = |x: f32, y: f32| {
+        let (dx, dy) = (x - wf * 0.5, y - hf * 0.5);
+        Point {
+            x: wf * 0.5 + dx * cos + dy * sin,
+            y: hf * 0.5 - dx * sin + dy * cos,
+        }
+    };
+    let quad = Quad {
+        a: corner(0.0, hf),
+        b: corner(0.0, 0.0),
+        c: corner(wf, 0.0),
+        d: corner(wf, hf),
+    };
+    perspective(source, quad, width, height, options)
+}
+
+pub fn deskew(source: &RGBAImage, options: &DeskewOptions, warp: &WarpOptions) -> RGBAImage {
+    let angle = skew_angle(source, options);
+    if angle == 0.0 {
+        RGBAImage {
+            data: source.data.clone(),
+            width: source.width,
+            height: source.height,
+        }
+    } else {
+        rotate_by(source, -angle, warp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+    use libm::floorf;
+
+    // a page of text lines rotated clockwise by degrees, antialiased by the distance of each pixel
+    // center from the edges of the words
+    fn page(degrees: f32) -> RGBAImage {
+        let (width, height) = (600, 800);
+        let rad = degrees * PI / 180.0;
+        let (sin, cos) = (sinf(rad), cosf(rad));
+        // coverage of a span start..end around v, one pixel wide
+        let span =
+            |v: f32, start: f32, end: f32| (v - start + 0.5).min(end - v + 0.5).clamp(0.0, 1.0);
+        let data: Vec<u8> = (0..width * height)
+            .flat_map(|i| {
+                // back into the unrotated page around its center
+                let (dx, dy) = ((i % width) as f32 - 300.0, (i / width) as f32 - 400.0);
+                let (x, y) = (300.0 + dx * cos + dy * sin, 400.0 - dx * sin + dy * cos);
+                let (line, word) = (floorf((y - 60.0) / 24.0), floorf((x - 60.0) / 40.0));
+                let top = 60.0 + line * 24.0;
+                let left = 60.0 + word * 40.0;
+                let ink = if (0.0..28.0).contains(&line) && (0.0..12.0).contains(&word) {
+                    span(y, top, top + 10.0) * span(x, left, left + 32.0)
+                } else {
+                    0.0
+                };
+                let v = (255.0 - ink * 220.0) as u8;
+                [v, v, v, 255]
+            })
+            .collect();
+        RGBAImage {
+            data,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn recovers_skew() {
+        for degrees in [-3.0, -1.5, 0.0, 0.3, 2.0, 4.0] {
+            let angle = page(degrees).skew_angle(&DeskewOptions::new());
+            assert!(
+                (angle - degrees).abs() < 0.3,
+                "skewed by {} but found {}",
+                degrees,
+                angle
+            );
+        }
+    }
+}
@@ -1,14 +1,14 @@
 use core::cmp::Ordering;
 
-use super::super::Image;
+use super::super::{bands, simd, Image};
 use super::{
-    consts::{
-        ANGS_PER_RAD, COS, GRADIENT_ERROR, GRADIENT_OFFSET, HOUGH_MATCH_RATIO, MAX_ANG_ERROR, SIN,
-    },
-    Point, Quad, ScoredQuad,
+    consts::{ANGS_PER_RAD, COS, SIN},
+    DetectOptions, Point, Quad, ScoredQuad,
 };
 use alloc::vec::Vec;
+use libm::{atan2f, atanf, hypotf, logf, powf};
 
+#[derive(Default)]
 pub struct GradientVotesResult {
     height: usize,
     width: usize,
@@ -16,19 +16,45 @@ pub struct GradientVotesResult {
     num_bins: usize,
     buf: Vec<f32>,
     grad_buf: Vec<f32>,
+    // votes and total gradient of each band when bands vote on separate threads
+    band_bufs: Vec<(Vec<f32>, f32)>,
     avg_grad: f32,
     max_grad: f32,
 }
 
-pub fn gradient_votes(source: &Image) -> GradientVotesResult {
+pub fn gradient_votes(source: &Image, options: &DetectOptions) -> GradientVotesResult {
+    let mut result = GradientVotesResult::default();
+    gradient_votes_into(source, options, &mut result);
+    result
+}
+
+// gradient_votes into a result whose buffers are reused
+pub fn gradient_votes_into(
+    source: &Image,
+    options: &DetectOptions,
+    result: &mut GradientVotesResult,
+) {
     let &Image {
         data: ref source,
         width,
         height,
     } = source;
+    let &DetectOptions {
+        gradient_error,
+        gradient_offset,
+        ..
+    } = options;
+    // without an interior pixel there is nothing to vote
+    if width < 3 || height < 3 {
+        result.buf.clear();
+        result.grad_buf.clear();
+        result.num_bins = 0;
+        result.max_grad = 0.0;
+        return;
+    }
     let hf = height as f32;
     let wf = width as f32;
-    let diag = hf.hypot(wf);
+    let diag = hypotf(hf, wf);
     let num_bins = diag as usize;
     let mh = height - 1;
     let mw = width - 1;
@@ -36,82 +62,115 @@ pub fn gradient_votes(source: &Image) -> GradientVotesResult {
     let southwest = width - 1;
     let south = width;
     let southeast = width + 1;
-    let mut buf = vec![0.0; num_bins << 8];
-    let mut grad_buf = vec![0.0; source.len()];
-    let mut total_grad = 0.0;
-    let mut max_grad = f32::NEG_INFINITY;
-    for i in 1..mh {
-        let ifl = i as f32;
-        let bi = i * width;
-        for j in 1..mw {
-            let jfl = j as f32;
-            let px = bi + j;
-            let nw = unsafe { *source.get_unchecked(px - southeast) };
-            let n = unsafe { *source.get_unchecked(px - south) };
-            let ne = unsafe { *source.get_unchecked(px - southwest) };
-            let w = unsafe { *source.get_unchecked(px - east) };
-            let e = unsafe { *source.get_unchecked(px + east) };
-            let sw = unsafe { *source.get_unchecked(px + southwest) };
-            let s = unsafe { *source.get_unchecked(px + south) };
-            let se = unsafe { *source.get_unchecked(px + southeast) };
-
-            let sx = 10.0 * (e - w) + 3.0 * (ne + se - nw - sw);
-            let sy = 10.0 * (n - s) + 3.0 * (ne + nw - se - sw);
-            let grad = (sx * sx + sy * sy).powf(0.3).max(0.0);
-            let angle_rad = (sy / sx).atan();
-            if !angle_rad.is_nan() {
-                let angle = (angle_rad * ANGS_PER_RAD + 128.0) as u8;
-                let ind = angle as usize;
-                let bin = (unsafe { *COS.get_unchecked(ind) } * ifl
-                    + unsafe { *SIN.get_unchecked(ind) } * jfl
-                    + diag) as usize
-                    >> 1;
-                let buf_ind = (bin << 8) | ind;
-                let loc = unsafe { buf.get_unchecked_mut(buf_ind) };
-                let val = *loc + grad / GRADIENT_OFFSET;
-                *loc = val;
-                max_grad = max_grad.max(val);
-                for off in 1..=GRADIENT_ERROR {
-                    let local_grad = grad / (off as f32 * off as f32 + GRADIENT_OFFSET);
-                    let approx = angle.wrapping_add(off);
-                    let ind = approx as usize;
-                    let bin = (unsafe { *COS.get_unchecked(ind) } * ifl
-                        + unsafe { *SIN.get_unchecked(ind) } * jfl
-                        + diag) as usize
-                        >> 1;
-                    let buf_ind = (bin << 8) | ind;
-                    let loc = unsafe { buf.get_unchecked_mut(buf_ind) };
-                    let val = *loc + local_grad;
-                    *loc = val;
+    // votes for the rows of grads, which start at row first, into buf; returns their total gradient
+    let vote = |first: usize, grads: &mut [f32], buf: &mut [f32]| {
+        let mut total_grad = 0.0;
+        let base = first * width;
+        for i in first.max(1)..(first + grads.len() / width).min(mh) {
+            let ifl = i as f32;
+            let bi = i * width;
+            for j in 1..mw {
+                let jfl = j as f32;
+                let px = bi + j;
+                let nw = unsafe { *source.get_unchecked(px - southeast) };
+                let n = unsafe { *source.get_unchecked(px - south) };
+                let ne = unsafe { *source.get_unchecked(px - southwest) };
+                let w = unsafe { *source.get_unchecked(px - east) };
+                let e = unsafe { *source.get_unchecked(px + east) };
+                let sw = unsafe { *source.get_unchecked(px + southwest) };
+                let s = unsafe { *source.get_unchecked(px + south) };
+                let se = unsafe { *source.get_unchecked(px + southeast) };
 
-                    let approx = angle.wrapping_sub(off);
-                    let ind = approx as usize;
+                let sx = 10.0 * (e - w) + 3.0 * (ne + se - nw - sw);
+                let sy = 10.0 * (n - s) + 3.0 * (ne + nw - se - sw);
+                let grad = powf(sx * sx + sy * sy, 0.3).max(0.0);
+                let angle_rad = atanf(sy / sx);
+                if !angle_rad.is_nan() {
+                    let angle = (angle_rad * ANGS_PER_RAD + 128.0) as u8;
+                    let ind = angle as usize;
                     let bin = (unsafe { *COS.get_unchecked(ind) } * ifl
                         + unsafe { *SIN.get_unchecked(ind) } * jfl
                         + diag) as usize
                         >> 1;
                     let buf_ind = (bin << 8) | ind;
                     let loc = unsafe { buf.get_unchecked_mut(buf_ind) };
-                    let val2 = *loc + local_grad;
-                    *loc = val2;
-                    max_grad = max_grad.max(val2.max(val));
+                    *loc += grad / gradient_offset;
+                    for off in 1..=gradient_error {
+                        let local_grad = grad / (off as f32 * off as f32 + gradient_offset);
+                        let approx = angle.wrapping_add(off);
+                        let ind = approx as usize;
+                        let bin = (unsafe { *COS.get_unchecked(ind) } * ifl
+                            + unsafe { *SIN.get_unchecked(ind) } * jfl
+                            + diag) as usize
+                            >> 1;
+                        let buf_ind = (bin << 8) | ind;
+                        let loc = unsafe { buf.get_unchecked_mut(buf_ind) };
+                        *loc += local_grad;
+
+                        let approx = angle.wrapping_sub(off);
+                        let ind = approx as usize;
+                        let bin = (unsafe { *COS.get_unchecked(ind) } * ifl
+                            + unsafe { *SIN.get_unchecked(ind) } * jfl
+                            + diag) as usize
+                            >> 1;
+                        let buf_ind = (bin << 8) | ind;
+                        let loc = unsafe { buf.get_unchecked_mut(buf_ind) };
+                        *loc += local_grad;
+                    }
                 }
+                unsafe { *grads.get_unchecked_mut(px - base) = grad };
+                total_grad += grad;
             }
-            unsafe { *grad_buf.get_unchecked_mut(px) = grad };
-            total_grad += grad;
         }
-    }
+        total_grad
+    };
+    result.buf.clear();
+    result.buf.resize(num_bins << 8, 0.0);
+    result.grad_buf.clear();
+    result.grad_buf.resize(source.len(), 0.0);
+    let total_grad = if bands::threads() == 1 {
+        vote(0, &mut result.grad_buf, &mut result.buf)
+    } else {
+        // every band votes into its own accumulator, and they are summed after
+        let band_rows = height.div_ceil(bands::threads());
+        let band_bufs = &mut result.band_bufs;
+        band_bufs.resize_with(height.div_ceil(band_rows), Default::default);
+        bands::rows_with(
+            &mut result.grad_buf,
+            width,
+            band_rows,
+            band_bufs,
+            |first, grads, (buf, total_grad)| {
+                buf.clear();
+                buf.resize(num_bins << 8, 0.0);
+                *total_grad = vote(first, grads, buf);
+            },
+        );
+        // summed a range of bins at a time, so that runs on every thread too
+        let band_bufs = &*band_bufs;
+        bands::rows(
+            &mut result.buf,
+            256,
+            bands::band_rows(256 * band_bufs.len()),
+            &mut (),
+            |first, bins, _| {
+                let start = first << 8;
+                for (buf, _) in band_bufs {
+                    simd::accumulate(bins, &buf[start..start + bins.len()], 1.0);
+                }
+            },
+        );
+        band_bufs.iter().map(|&(_, total_grad)| total_grad).sum()
+    };
+    // votes only ever grow, so this is the largest any bin reached
+    let max_grad = result.buf.iter().copied().fold(f32::NEG_INFINITY, f32::max);
     let avg_grad = total_grad / ((hf - 2.0) * (wf - 2.0));
-    GradientVotesResult {
-        height,
-        width,
-        diag,
-        num_bins,
-        buf,
-        grad_buf,
-        avg_grad,
-        max_grad,
-    }
+    result.height = height;
+    result.width = width;
+    result.diag = diag;
+    result.num_bins = num_bins;
+    result.avg_grad = avg_grad;
+    result.max_grad = max_grad;
 }
 
 // use wasm_bindgen::prelude::*;
@@ -137,7 +196,7 @@ impl PartialEq for Line {
 impl Eq for Line {}
 impl PartialOrd for Line {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.score.partial_cmp(&other.score)
+        Some(self.cmp(other))
     }
 }
 impl Ord for Line {
@@ -146,8 +205,20 @@ impl Ord for Line {
     }
 }
 
-pub fn edges(result: &GradientVotesResult, threshold: f32) -> Vec<Line> {
-    assert!((0.0..1.0).contains(&threshold));
+pub fn edges(result: &GradientVotesResult, options: &DetectOptions) -> Vec<Line> {
+    let mut lines = Vec::new();
+    edges_into(result, options, &mut lines);
+    lines
+}
+
+// edges into a list that is reused
+pub fn edges_into(result: &GradientVotesResult, options: &DetectOptions, lines: &mut Vec<Line>) {
+    let &DetectOptions {
+        edge_threshold: threshold,
+        hough_match_ratio,
+        max_ang_error,
+        ..
+    } = options;
     let &GradientVotesResult {
         diag,
         num_bins,
@@ -156,7 +227,7 @@ pub fn edges(result: &GradientVotesResult, threshold: f32) -> Vec<Line> {
         ..
     } = result;
     let threshold_val = threshold * max_grad;
-    let mut lines = Vec::new();
+    lines.clear();
     for bin in 0..num_bins {
         for angle in 0..256 {
             let val = unsafe { *buf.get_unchecked((bin << 8) | angle) };
@@ -171,7 +242,7 @@ pub fn edges(result: &GradientVotesResult, threshold: f32) -> Vec<Line> {
     }
     lines.truncate(5000);
     lines.sort_unstable_by(|a, b| b.cmp(a));
-    let max_bin_err = (diag * HOUGH_MATCH_RATIO + 1.0) as usize;
+    let max_bin_err = (diag * hough_match_ratio + 1.0) as usize;
     let mut i = 0;
     while i < lines.len() {
         let Line {
@@ -188,7 +259,7 @@ pub fn edges(result: &GradientVotesResult, threshold: f32) -> Vec<Line> {
             } = lines[j];
             let angle_diff = angle.wrapping_sub(a2);
             if bin.abs_diff(b2) <= max_bin_err
-                && angle_diff.min(0u8.wrapping_sub(angle_diff)) <= MAX_ANG_ERROR
+                && angle_diff.min(0u8.wrapping_sub(angle_diff)) <= max_ang_error
             {
                 lines.remove(j);
                 score += s2;
@@ -199,16 +270,161 @@ pub fn edges(result: &GradientVotesResult, threshold: f32) -> Vec<Line> {
         lines[i].score = score;
         i += 1;
     }
+}
+
+// Hough (angle, bin) of the line through two points, matching gradient_votes
+fn line_through(a: Point, b: Point, diag: f32) -> (u8, f32) {
+    let mut sin = a.y - b.y;
+    let mut cos = b.x - a.x;
+    if sin < 0.0 {
+        sin = -sin;
+        cos = -cos;
+    }
+    let angle = ((atan2f(sin, cos) * ANGS_PER_RAD + 0.5) as usize) & 255;
+    let rho =
+        a.x * unsafe { *SIN.get_unchecked(angle) } + a.y * unsafe { *COS.get_unchecked(angle) };
+    (angle as u8, (rho + diag) * 0.5)
+}
+
+// angle in bins, within max_offset of the axes, at which the votes are most concentrated into
+// few lines; rows of text pile up in a handful of bins once the angle matches them
+pub fn dominant_angle(result: &GradientVotesResult, max_offset: u8) -> f32 {
+    let &GradientVotesResult {
+        num_bins, ref buf, ..
+    } = result;
+    let max_offset = max_offset.min(63);
+    let energy = |offset: i32| {
+        let horizontal = (offset & 255) as usize;
+        let vertical = ((offset + 128) & 255) as usize;
+        (0..num_bins)
+            .map(|bin| {
+                let h = buf[(bin << 8) | horizontal];
+                let v = buf[(bin << 8) | vertical];
+                h * h + v * v
+            })
+            .sum::<f32>()
+    };
+    let m = max_offset as i32;
+    let scores: Vec<f32> = (-m - 1..=m + 1).map(energy).collect();
+    let mut best = 1;
+    for i in 2..scores.len() - 1 {
+        if scores[i] > scores[best] {
+            best = i;
+        }
+    }
+    // parabola through the logarithms of the peak and its neighbors for a sub-bin estimate
+    let (l, c, r) = (
+        logf(scores[best - 1].max(f32::MIN_POSITIVE)),
+        logf(scores[best].max(f32::MIN_POSITIVE)),
+        logf(scores[best + 1].max(f32::MIN_POSITIVE)),
+    );
+    let denom = l - 2.0 * c + r;
+    let shift = if denom < 0.0 {
+        (0.5 * (l - r) / denom).clamp(-0.5, 0.5)
+    } else {
+        0.0
+    };
+    // gradient_votes truncates angles into their bins, so the votes of a bin lie half a bin above it
+    (best as i32 - m - 1) as f32 + shift + 0.5
+}
+
+// edges, but only those close to one of the sides of a previously found quad
+pub fn edges_near(
+    result: &GradientVotesResult,
+    options: &DetectOptions,
+    prior: Quad,
+    max_dist: f32,
+    max_ang_err: u8,
+) -> Vec<Line> {
+    let mut lines = Vec::new();
+    edges_near_into(result, options, prior, max_dist, max_ang_err, &mut lines);
     lines
 }
 
+// edges_near into a list that is reused
+pub fn edges_near_into(
+    result: &GradientVotesResult,
+    options: &DetectOptions,
+    prior: Quad,
+    max_dist: f32,
+    max_ang_err: u8,
+    lines: &mut Vec<Line>,
+) {
+    let diag = result.diag;
+    let Quad { a, b, c, d } = prior;
+    let sides = [
+        line_through(a, b, diag),
+        line_through(b, c, diag),
+        line_through(c, d, diag),
+        line_through(d, a, diag),
+    ];
+    let max_bin_err = max_dist * 0.5;
+    edges_into(result, options, lines);
+    lines.retain(|line| {
+        sides.iter().any(|&(angle, bin)| {
+            let angle_diff = line.angle.wrapping_sub(angle);
+            if angle_diff.min(0u8.wrapping_sub(angle_diff)) > max_ang_err {
+                return false;
+            }
+            // wrapping past 0 or 255 flips the sign of the offset
+            let bin = if line.angle.abs_diff(angle) > 128 {
+                diag - bin
+            } else {
+                bin
+            };
+            (line.bin as f32 - bin).abs() <= max_bin_err
+        })
+    });
+}
+
 #[inline]
 fn right_err(l1: Line, l2: Line) -> f32 {
     let err = l1.angle.wrapping_sub(l2.angle).abs_diff(128) as f32;
     err * err + 3.0
 }
 
-pub fn documents(result: &GradientVotesResult, lines: &[Line]) -> Vec<ScoredQuad> {
+// squared cosine of the angle at corner b; 0 for a right angle
+#[inline]
+fn corner_cos2(a: Point, b: Point, c: Point) -> f32 {
+    let (ux, uy) = (a.x - b.x, a.y - b.y);
+    let (vx, vy) = (c.x - b.x, c.y - b.y);
+    let dot = ux * vx + uy * vy;
+    let len2 = (ux * ux + uy * uy) * (vx * vx + vy * vy);
+    if len2 == 0.0 {
+        1.0
+    } else {
+        dot * dot / len2
+    }
+}
+
+#[inline]
+pub(super) fn area(quad: Quad) -> f32 {
+    let Quad { a, b, c, d } = quad;
+    ((a.x * b.y - b.x * a.y)
+        + (b.x * c.y - c.x * b.y)
+        + (c.x * d.y - d.x * c.y)
+        + (d.x * a.y - a.x * d.y))
+        .abs()
+        * 0.5
+}
+
+pub fn documents(
+    result: &GradientVotesResult,
+    lines: &[Line],
+    options: &DetectOptions,
+) -> Vec<ScoredQuad> {
+    let mut quads = Vec::new();
+    documents_into(result, lines, options, &mut quads);
+    quads
+}
+
+// documents into a list that is reused
+pub fn documents_into(
+    result: &GradientVotesResult,
+    lines: &[Line],
+    options: &DetectOptions,
+    quads: &mut Vec<ScoredQuad>,
+) {
     let &GradientVotesResult {
         width,
         height,
@@ -217,6 +433,11 @@ pub fn documents(result: &GradientVotesResult, lines: &[Line]) -> Vec<ScoredQuad
         avg_grad,
         ..
     } = result;
+    let &DetectOptions {
+        max_radius,
+        min_area,
+        ..
+    } = options;
     let hf = height as f32;
     let wf = width as f32;
     let intersection = |l1: Line, l2: Line| {
@@ -235,12 +456,13 @@ pub fn documents(result: &GradientVotesResult, lines: &[Line]) -> Vec<ScoredQuad
         let xr = x / wf - 0.5;
         let yr = y / hf - 0.5;
 
-        (Point { x, y }, xr * xr + yr * yr <= 0.55)
+        (Point { x, y }, xr * xr + yr * yr <= max_radius)
     };
     let iw = width as isize;
     let ih = height as isize;
     let score_between = |a: Point, b: Point| {
         let mut score = 0.0;
+        let mut covered = 0;
 
         let xf = b.x as isize;
         let yf = b.y as isize;
@@ -257,7 +479,11 @@ pub fn documents(result: &GradientVotesResult, lines: &[Line]) -> Vec<ScoredQuad
 
         while x != xf || y != yf {
             if 0 <= x && 0 <= y && x < iw && y < ih {
-                score += unsafe { *grad_buf.as_ptr().offset(y * iw + x) } - avg_grad;
+                let grad = unsafe { *grad_buf.as_ptr().offset(y * iw + x) };
+                score += grad - avg_grad;
+                if grad > avg_grad {
+                    covered += 1;
+                }
             }
 
             let e2 = error << 1;
@@ -271,29 +497,43 @@ pub fn documents(result: &GradientVotesResult, lines: &[Line]) -> Vec<ScoredQuad
             }
         }
 
-        (score * ((dx - dy) as f32).powf(-0.3)).max(0.0)
+        let len = (dx - dy) as f32;
+        // the loop takes one step per pixel along the longer axis, diagonal moves included
+        let steps = dx.max(-dy).max(1) as f32;
+        ((score * powf(len, -0.3)).max(0.0), covered as f32 / steps)
     };
+    let frame_area = wf * hf;
     let scored_quad = |quad: Quad, l1: Line, l2: Line, l3: Line, l4: Line| {
-        let edge_total = score_between(quad.a, quad.b)
-            + score_between(quad.b, quad.c)
-            + score_between(quad.c, quad.d)
-            + score_between(quad.d, quad.a);
-        let edge_score = edge_total.powf(3.0);
+        let (s1, c1) = score_between(quad.a, quad.b);
+        let (s2, c2) = score_between(quad.b, quad.c);
+        let (s3, c3) = score_between(quad.c, quad.d);
+        let (s4, c4) = score_between(quad.d, quad.a);
+        let edge_score = powf(s1 + s2 + s3 + s4, 3.0);
 
         let e12 = right_err(l1, l2);
         let e23 = right_err(l2, l3);
         let e34 = right_err(l3, l4);
         let e41 = right_err(l4, l1);
-        let angle_score = (e12 * e12 + e23 * e23 + e34 * e34 + e41 * e41).powf(-0.1);
+        let angle_score = powf(e12 * e12 + e23 * e23 + e34 * e34 + e41 * e41, -0.1);
 
-        let line_score = (l1.score * l2.score * l3.score * l4.score).powf(0.1);
+        let line_score = powf(l1.score * l2.score * l3.score * l4.score, 0.1);
+
+        let coverage = (c1 + c2 + c3 + c4) * 0.25;
+        let right = 1.0
+            - (corner_cos2(quad.d, quad.a, quad.b)
+                + corner_cos2(quad.a, quad.b, quad.c)
+                + corner_cos2(quad.b, quad.c, quad.d)
+                + corner_cos2(quad.c, quad.d, quad.a))
+                * 0.25;
+        let area = (area(quad) / frame_area / min_area).min(1.0);
 
         ScoredQuad {
             quad,
             score: edge_score * angle_score * line_score,
+            confidence: coverage * right * area,
         }
     };
-    let mut quads = Vec::new();
+    quads.clear();
     for (i, &l1) in lines.iter().enumerate() {
         for (j, &l2) in lines.iter().enumerate().skip(i + 1) {
             let (i12, i12b) = intersection(l1, l2);
@@ -369,5 +609,4 @@ pub fn documents(result: &GradientVotesResult, lines: &[Line]) -> Vec<ScoredQuad
         }
     }
     quads.sort_unstable_by(|a, b| b.cmp(a));
-    quads
 }
@@ -1,21 +1,36 @@
 use core::cmp::Ordering;
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 
+mod aspect;
 mod consts;
+mod deskew;
 mod detect;
+mod multi;
 mod perspective;
+mod refine;
 
+use consts::{
+    DUPLICATE_DISTANCE, EDGE_THRESHOLD, GRADIENT_ERROR, GRADIENT_OFFSET, HOUGH_MATCH_RATIO,
+    MAX_ANG_ERROR, MAX_LINES, MAX_OVERLAP, MAX_RADIUS, MIN_AREA, MIN_CONFIDENCE, MIN_RESOLUTION,
+    MULTI_MIN_AREA, REFINE_BAND, RESOLUTION,
+};
+
+pub use aspect::*;
+pub use deskew::*;
 pub use detect::*;
+pub use multi::*;
 pub use perspective::*;
+pub use refine::*;
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 #[derive(Clone, Copy)]
 pub struct Point {
     pub x: f32,
     pub y: f32,
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 #[derive(Clone, Copy)]
 pub struct Quad {
     pub a: Point,
@@ -24,9 +39,10 @@ pub struct Quad {
     pub d: Point,
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl Quad {
-    #[wasm_bindgen(constructor)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    #[allow(clippy::too_many_arguments)]
     pub fn new(ax: f32, ay: f32, bx: f32, by: f32, cx: f32, cy: f32, dx: f32, dy: f32) -> Quad {
         Quad {
             a: Point { x: ax, y: ay },
@@ -37,10 +53,89 @@ impl Quad {
     }
 }
 
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Copy)]
+pub struct DetectOptions {
+    // max angle bins away from the measured gradient direction that still get votes
+    pub gradient_error: u8,
+    // damping for the vote falloff over those neighboring angles
+    pub gradient_offset: f32,
+    // fraction of the image diagonal within which Hough lines are merged
+    pub hough_match_ratio: f32,
+    // max angle bins between Hough lines that are merged
+    pub max_ang_error: u8,
+    // fraction of the strongest Hough vote a line needs to be considered
+    pub edge_threshold: f32,
+    // number of candidate lines that are combined into quads
+    pub max_lines: usize,
+    // length of the shorter side the image is downscaled to before detection
+    pub resolution: f32,
+    // max squared distance of a corner from the center, relative to the image size
+    pub max_radius: f32,
+    // corner distance, relative to the image diagonal, under which two quads are the same
+    pub duplicate_distance: f32,
+    // fraction of the frame below which smaller quads lose confidence
+    pub min_area: f32,
+    // distance in detection pixels searched around each side for sub-pixel refinement; 0 to skip
+    pub refine_band: f32,
+    // fraction of a document that may be covered by another when finding several at once
+    pub max_overlap: f32,
+    // confidence every document needs when finding several at once
+    pub min_confidence: f32,
+    // min_area when finding several at once, low enough for receipts and business cards
+    pub multi_min_area: f32,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl DetectOptions {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new() -> DetectOptions {
+        DetectOptions {
+            gradient_error: GRADIENT_ERROR,
+            gradient_offset: GRADIENT_OFFSET,
+            hough_match_ratio: HOUGH_MATCH_RATIO,
+            max_ang_error: MAX_ANG_ERROR,
+            edge_threshold: EDGE_THRESHOLD,
+            max_lines: MAX_LINES,
+            resolution: RESOLUTION,
+            max_radius: MAX_RADIUS,
+            duplicate_distance: DUPLICATE_DISTANCE,
+            min_area: MIN_AREA,
+            refine_band: REFINE_BAND,
+            max_overlap: MAX_OVERLAP,
+            min_confidence: MIN_CONFIDENCE,
+            multi_min_area: MULTI_MIN_AREA,
+        }
+    }
+}
+
+impl DetectOptions {
+    // the options pulled back into the range detection works in, so values set from JS can't
+    // make it panic
+    pub fn clamped(&self) -> DetectOptions {
+        DetectOptions {
+            // votes are divided by it, and a 0 turns empty bins into NaN
+            gradient_offset: self.gradient_offset.max(0.1),
+            edge_threshold: self.edge_threshold.clamp(0.0, 1.0),
+            resolution: self.resolution.max(MIN_RESOLUTION),
+            ..*self
+        }
+    }
+}
+
+impl Default for DetectOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 #[derive(Clone, Copy)]
 pub struct ScoredQuad {
     pub quad: Quad,
     pub score: f32,
+    // 0-1 from edge coverage, corner squareness and size relative to the frame
+    pub confidence: f32,
 }
 
 impl PartialEq for ScoredQuad {
@@ -51,7 +146,7 @@ impl PartialEq for ScoredQuad {
 impl Eq for ScoredQuad {}
 impl PartialOrd for ScoredQuad {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.score.partial_cmp(&other.score)
+        Some(self.cmp(other))
     }
 }
 impl Ord for ScoredQuad {
@@ -0,0 +1,83 @@
+use super::{detect::area, Point, Quad, ScoredQuad};
+use alloc::vec::Vec;
+
+fn cross(o: Point, a: Point, b: Point) -> f32 {
+    (a.x - o.x) * (b.y - o.y) - (a.y - o.y) * (b.x - o.x)
+}
+
+fn polygon_area(pts: &[Point]) -> f32 {
+    let mut sum = 0.0;
+    for (i, p) in pts.iter().enumerate() {
+        let q = pts[(i + 1) % pts.len()];
+        sum += p.x * q.y - q.x * p.y;
+    }
+    sum.abs() * 0.5
+}
+
+// Sutherland-Hodgman clipping of one convex quad against another
+fn intersection_area(q1: Quad, q2: Quad) -> f32 {
+    let clip = [q2.a, q2.b, q2.c, q2.d];
+    let sign = if cross(clip[0], clip[1], clip[2]) + cross(clip[0], clip[2], clip[3]) < 0.0 {
+        -1.0
+    } else {
+        1.0
+    };
+    let mut poly = vec![q1.a, q1.b, q1.c, q1.d];
+    let mut next = Vec::with_capacity(8);
+    for (i, &e1) in clip.iter().enumerate() {
+        let e2 = clip[(i + 1) & 3];
+        next.clear();
+        for (j, &p) in poly.iter().enumerate() {
+            let q = poly[(j + 1) % poly.len()];
+            let dp = cross(e1, e2, p) * sign;
+            let dq = cross(e1, e2, q) * sign;
+            if dp >= 0.0 {
+                next.push(p);
+            }
+            if (dp >= 0.0) != (dq >= 0.0) {
+                let t = dp / (dp - dq);
+                next.push(Point {
+                    x: p.x + (q.x - p.x) * t,
+                    y: p.y + (q.y - p.y) * t,
+                });
+            }
+        }
+        core::mem::swap(&mut poly, &mut next);
+        if poly.len() < 3 {
+            return 0.0;
+        }
+    }
+    polygon_area(&poly)
+}
+
+// fraction of the smaller quad covered by the other
+pub fn overlap(q1: Quad, q2: Quad) -> f32 {
+    let smaller = area(q1).min(area(q2));
+    if smaller <= 0.0 {
+        return 1.0;
+    }
+    intersection_area(q1, q2) / smaller
+}
+
+// greedily keeps the best quads that don't overlap any already kept
+pub fn separate(
+    quads: &[ScoredQuad],
+    max_overlap: f32,
+    min_confidence: f32,
+    max: usize,
+) -> Vec<ScoredQuad> {
+    let mut kept: Vec<ScoredQuad> = Vec::new();
+    for &doc in quads {
+        if kept.len() >= max {
+            break;
+        }
+        if doc.confidence >= min_confidence
+            && kept
+                .iter()
+                .all(|other| overlap(doc.quad, other.quad) <= max_overlap)
+        {
+            kept.push(doc);
+        }
+    }
+    kept
+}
@@ -1,5 +1,11 @@
-use super::{super::RGBAImage, Point, Quad};
-use alloc::vec::Vec;
+use super::{
+    super::{bands, grayscale::luminance, BitImage, ColorOptions, GrayImage, Image, RGBAImage},
+    DeskewOptions, Point, Quad,
+};
+use core::f32::consts::PI;
+use libm::{ceilf, floorf, hypotf, sinf};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
 
 type Vec3 = [f32; 3];
 type Mat3 = [f32; 9];
@@ -65,10 +71,241 @@ fn create_projector(from: Quad, to: Quad) -> impl Fn(Point) -> Point {
     }
 }
 
-pub fn perspective(source: &RGBAImage, quad: Quad, width: usize, height: usize) -> RGBAImage {
-    let mut data = Vec::with_capacity((width * height) << 2);
-    unsafe {
-        data.set_len(data.capacity());
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum Interpolation {
+    Nearest,
+    #[default]
+    Bilinear,
+    Bicubic,
+    Lanczos3,
+    // averages several bilinear samples over each output pixel's footprint when minifying
+    Area,
+}
+
+// what is sampled outside the source image
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum Fill {
+    #[default]
+    Solid,
+    Transparent,
+    Clamp,
+    Mirror,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Copy)]
+pub struct WarpOptions {
+    pub interpolation: Interpolation,
+    pub fill: Fill,
+    // 0xRRGGBBAA color used for Fill::Solid
+    pub fill_color: u32,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl WarpOptions {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new() -> WarpOptions {
+        WarpOptions {
+            interpolation: Interpolation::Bilinear,
+            fill: Fill::Solid,
+            fill_color: 0xFFFFFFFF,
+        }
+    }
+}
+
+impl Default for WarpOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Copy)]
+pub struct ExtractOptions {
+    pub warp: WarpOptions,
+    // camera focal length in pixels for aspect ratio recovery; 0 to estimate it
+    pub focal_length: f32,
+    pub snap_paper: bool,
+    // relative aspect ratio error within which a page is snapped to a paper size
+    pub snap_tolerance: f32,
+    // straighten content that is slightly rotated within the page
+    pub deskew: bool,
+    pub skew: DeskewOptions,
+    // turn the page by quarter turns so its text is upright
+    pub auto_rotate: bool,
+    // orientation confidence needed before the page is turned
+    pub rotate_confidence: f32,
+    // white balance and contrast stretch the page so it looks scanned
+    pub correct_color: bool,
+    pub color: ColorOptions,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl ExtractOptions {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new() -> ExtractOptions {
+        ExtractOptions {
+            warp: WarpOptions::new(),
+            focal_length: 0.0,
+            snap_paper: false,
+            snap_tolerance: 0.05,
+            deskew: false,
+            skew: DeskewOptions::new(),
+            auto_rotate: false,
+            rotate_confidence: 0.1,
+            correct_color: false,
+            color: ColorOptions::new(),
+        }
+    }
+}
+
+impl Default for ExtractOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// max samples per axis for area interpolation
+const MAX_AREA_SAMPLES: usize = 16;
+
+#[inline]
+fn reflect(v: isize, len: isize) -> usize {
+    let period = len << 1;
+    let m = v.rem_euclid(period);
+    (if m >= len { period - 1 - m } else { m }) as usize
+}
+
+// accumulates premultiplied RGBA so transparent or filled texels don't bleed their color
+struct Sampler<'a> {
+    source: &'a RGBAImage,
+    fill: Fill,
+    color: [f32; 4],
+}
+
+impl Sampler<'_> {
+    #[inline]
+    fn texel(&self, x: isize, y: isize, weight: f32, acc: &mut [f32; 4]) {
+        let RGBAImage {
+            ref data,
+            width,
+            height,
+        } = *self.source;
+        let (xi, yi) = if (x as usize) < width && (y as usize) < height {
+            (x as usize, y as usize)
+        } else {
+            match self.fill {
+                Fill::Clamp => (
+                    x.clamp(0, width as isize - 1) as usize,
+                    y.clamp(0, height as isize - 1) as usize,
+                ),
+                Fill::Mirror => (reflect(x, width as isize), reflect(y, height as isize)),
+                Fill::Solid | Fill::Transparent => {
+                    let alpha = self.color[3] * weight;
+                    for (val, &c) in acc.iter_mut().zip(self.color.iter()).take(3) {
+                        *val += c * alpha;
+                    }
+                    acc[3] += alpha;
+                    return;
+                }
+            }
+        };
+        let base = (yi * width + xi) << 2;
+        let alpha = data[base + 3] as f32 * weight;
+        for i in 0..3 {
+            acc[i] += data[base + i] as f32 * alpha;
+        }
+        acc[3] += alpha;
+    }
+
+    #[inline]
+    fn nearest(&self, x: f32, y: f32, acc: &mut [f32; 4]) {
+        self.texel(floorf(x + 0.5) as isize, floorf(y + 0.5) as isize, 1.0, acc);
+    }
+
+    #[inline]
+    fn bilinear(&self, x: f32, y: f32, weight: f32, acc: &mut [f32; 4]) {
+        let xf = floorf(x);
+        let yf = floorf(y);
+        let xt = x - xf;
+        let yt = y - yf;
+        let xi = xf as isize;
+        let yi = yf as isize;
+        self.texel(xi, yi, (1.0 - xt) * (1.0 - yt) * weight, acc);
+        self.texel(xi + 1, yi, xt * (1.0 - yt) * weight, acc);
+        self.texel(xi, yi + 1, (1.0 - xt) * yt * weight, acc);
+        self.texel(xi + 1, yi + 1, xt * yt * weight, acc);
+    }
+
+    // separable filter over a (2 * radius)^2 neighborhood
+    fn filtered(&self, x: f32, y: f32, radius: isize, kernel: fn(f32) -> f32, acc: &mut [f32; 4]) {
+        let xf = floorf(x) as isize;
+        let yf = floorf(y) as isize;
+        let taps = (radius << 1) as usize;
+        let mut wx = [0.0; 6];
+        let mut wy = [0.0; 6];
+        let mut sx = 0.0;
+        let mut sy = 0.0;
+        for k in 0..taps {
+            let off = k as isize - radius + 1;
+            wx[k] = kernel(x - (xf + off) as f32);
+            wy[k] = kernel(y - (yf + off) as f32);
+            sx += wx[k];
+            sy += wy[k];
+        }
+        let norm = 1.0 / (sx * sy);
+        for (ky, &wyk) in wy.iter().enumerate().take(taps) {
+            let yi = yf + ky as isize - radius + 1;
+            for (kx, &wxk) in wx.iter().enumerate().take(taps) {
+                let xi = xf + kx as isize - radius + 1;
+                self.texel(xi, yi, wxk * wyk * norm, acc);
+            }
+        }
+    }
+}
+
+// Catmull-Rom
+fn cubic(x: f32) -> f32 {
+    let x = x.abs();
+    if x < 1.0 {
+        (1.5 * x - 2.5) * x * x + 1.0
+    } else if x < 2.0 {
+        ((-0.5 * x + 2.5) * x - 4.0) * x + 2.0
+    } else {
+        0.0
+    }
+}
+
+fn lanczos3(x: f32) -> f32 {
+    let x = x.abs();
+    if x < 1e-5 {
+        1.0
+    } else if x < 3.0 {
+        let px = PI * x;
+        3.0 * sinf(px) * sinf(px / 3.0) / (px * px)
+    } else {
+        0.0
+    }
+}
+
+// samples the quad into a width by height grid stored in data as rows of row_len items, calling
+// emit with the output row, each pixel's x and its unpremultiplied color and alpha from 0 to 255;
+// fully transparent pixels are skipped
+#[allow(clippy::too_many_arguments)]
+fn warp<T: Send>(
+    source: &RGBAImage,
+    quad: Quad,
+    width: usize,
+    height: usize,
+    options: &WarpOptions,
+    data: &mut [T],
+    row_len: usize,
+    emit: impl Fn(&mut [T], usize, [f32; 4]) + Sync + Send,
+) {
+    // an empty target has no rows to split into bands
+    if width == 0 || height == 0 {
+        return;
     }
     let wf = width as f32;
     let hf = height as f32;
@@ -81,42 +318,191 @@ pub fn perspective(source: &RGBAImage, quad: Quad, width: usize, height: usize)
         },
         quad,
     );
-    let off_sw = source.width << 2;
-    let off_se = off_sw + 4;
-    for y in 0..height {
-        let ib = y * width;
-        for x in 0..width {
-            let pt = projector(Point {
-                x: x as f32,
-                y: y as f32,
-            });
-            let xf = pt.x as usize;
-            let yf = pt.y as usize;
-            let dest_base = (ib + x) << 2;
-            data[dest_base + 3] = 255;
-            if xf + 1 < source.width && yf + 1 < source.height {
-                let xt = pt.x.fract();
-                let xtr = 1.0 - xt;
-                let yt = pt.y.fract();
-                let ytr = 1.0 - yt;
-                let raw_base = (yf * source.width + xf) << 2;
-                for i in 0..3 {
-                    let base = raw_base + i;
-                    let a = (source.data[base] as f32) * xtr + (source.data[base + 4] as f32) * xt;
-                    let b = (source.data[base + off_sw] as f32) * xtr
-                        + (source.data[base + off_se] as f32) * xt;
-                    data[dest_base + i] = (a * ytr + b * yt) as u8;
+    let color = options.fill_color.to_be_bytes();
+    let sampler = Sampler {
+        source,
+        fill: options.fill,
+        color: match options.fill {
+            Fill::Transparent => [0.0; 4],
+            _ => [
+                color[0] as f32,
+                color[1] as f32,
+                color[2] as f32,
+                color[3] as f32,
+            ],
+        },
+    };
+    let sample = |xf: f32, yf: f32| {
+        let pt = projector(Point { x: xf, y: yf });
+        let mut acc = [0.0; 4];
+        match options.interpolation {
+            Interpolation::Nearest => sampler.nearest(pt.x, pt.y, &mut acc),
+            Interpolation::Bilinear => sampler.bilinear(pt.x, pt.y, 1.0, &mut acc),
+            Interpolation::Bicubic => sampler.filtered(pt.x, pt.y, 2, cubic, &mut acc),
+            Interpolation::Lanczos3 => sampler.filtered(pt.x, pt.y, 3, lanczos3, &mut acc),
+            Interpolation::Area => {
+                let right = projector(Point { x: xf + 1.0, y: yf });
+                let down = projector(Point { x: xf, y: yf + 1.0 });
+                let nx = (ceilf(hypotf(right.x - pt.x, right.y - pt.y)) as usize)
+                    .clamp(1, MAX_AREA_SAMPLES);
+                let ny = (ceilf(hypotf(down.x - pt.x, down.y - pt.y)) as usize)
+                    .clamp(1, MAX_AREA_SAMPLES);
+                if nx == 1 && ny == 1 {
+                    sampler.bilinear(pt.x, pt.y, 1.0, &mut acc);
+                } else {
+                    let weight = 1.0 / (nx * ny) as f32;
+                    for sy in 0..ny {
+                        let oy = (sy as f32 + 0.5) / ny as f32 - 0.5;
+                        for sx in 0..nx {
+                            let ox = (sx as f32 + 0.5) / nx as f32 - 0.5;
+                            let spt = projector(Point {
+                                x: xf + ox,
+                                y: yf + oy,
+                            });
+                            sampler.bilinear(spt.x, spt.y, weight, &mut acc);
+                        }
+                    }
                 }
-            } else {
-                data[dest_base] = 255;
-                data[dest_base + 1] = 255;
-                data[dest_base + 2] = 255;
             }
         }
-    }
+        acc
+    };
+    bands::rows(
+        data,
+        row_len,
+        bands::band_rows(width << 2),
+        &mut (),
+        |first, band, _| {
+            for (y, row) in (first..).zip(band.chunks_exact_mut(row_len)) {
+                for x in 0..width {
+                    let acc = sample(x as f32, y as f32);
+                    let alpha = acc[3];
+                    if alpha > 0.0 {
+                        let unmul = 1.0 / alpha;
+                        emit(
+                            row,
+                            x,
+                            [acc[0] * unmul, acc[1] * unmul, acc[2] * unmul, alpha],
+                        );
+                    }
+                }
+            }
+        },
+    );
+}
+
+pub fn perspective(
+    source: &RGBAImage,
+    quad: Quad,
+    width: usize,
+    height: usize,
+    options: &WarpOptions,
+) -> RGBAImage {
+    let mut data = vec![0; (width * height) << 2];
+    warp(
+        source,
+        quad,
+        width,
+        height,
+        options,
+        &mut data,
+        width << 2,
+        |row, x, px| {
+            for (out, v) in row[x << 2..(x + 1) << 2].iter_mut().zip(px) {
+                *out = (v + 0.5) as u8;
+            }
+        },
+    );
     RGBAImage {
         data,
         width,
         height,
     }
 }
+
+// perspective straight to luminance, skipping the RGBA copy
+pub fn perspective_gray(
+    source: &RGBAImage,
+    quad: Quad,
+    width: usize,
+    height: usize,
+    options: &WarpOptions,
+) -> Image {
+    let mut data = vec![0.0; width * height];
+    warp(
+        source,
+        quad,
+        width,
+        height,
+        options,
+        &mut data,
+        width,
+        |row, x, px| {
+            row[x] = luminance(px);
+        },
+    );
+    Image {
+        data,
+        width,
+        height,
+    }
+}
+
+pub fn perspective_gray8(
+    source: &RGBAImage,
+    quad: Quad,
+    width: usize,
+    height: usize,
+    options: &WarpOptions,
+) -> GrayImage {
+    let mut data = vec![0; width * height];
+    warp(
+        source,
+        quad,
+        width,
+        height,
+        options,
+        &mut data,
+        width,
+        |row, x, px| {
+            row[x] = (luminance(px).min(1.0) * 255.0 + 0.5) as u8;
+        },
+    );
+    GrayImage {
+        data,
+        width,
+        height,
+    }
+}
+
+// pixels with luminance below the threshold (0-1) become ink, and transparent ones paper
+pub fn perspective_bits(
+    source: &RGBAImage,
+    quad: Quad,
+    width: usize,
+    height: usize,
+    options: &WarpOptions,
+    threshold: f32,
+) -> BitImage {
+    let stride = width.div_ceil(8);
+    let mut data = vec![0xFF; stride * height];
+    warp(
+        source,
+        quad,
+        width,
+        height,
+        options,
+        &mut data,
+        stride,
+        |row, x, px| {
+            if luminance(px) < threshold {
+                row[x >> 3] &= !(0x80 >> (x & 7));
+            }
+        },
+    );
+    BitImage {
+        data,
+        width,
+        height,
+    }
+}
@@ -0,0 +1,167 @@
+use super::{super::RGBAImage, Point, Quad};
+use alloc::vec::Vec;
+use libm::{atan2f, cosf, hypotf, sinf};
+
+// most samples taken along a single side
+const MAX_SAMPLES: f32 = 512.0;
+// fraction of each side skipped near the corners, where the other edge interferes
+const CORNER_MARGIN: f32 = 0.1;
+const MIN_POINTS: usize = 8;
+
+// bilinear luminance with the same weights as grayscale, scaled to 0-255
+fn luma(source: &RGBAImage, x: f32, y: f32) -> Option<f32> {
+    if x < 0.0 || y < 0.0 {
+        return None;
+    }
+    let xf = x as usize;
+    let yf = y as usize;
+    if xf + 1 >= source.width || yf + 1 >= source.height {
+        return None;
+    }
+    let xt = x - xf as f32;
+    let yt = y - yf as f32;
+    let px = |base: usize| {
+        (source.data[base] as f32) * 0.299
+            + (source.data[base + 1] as f32) * 0.587
+            + (source.data[base + 2] as f32) * 0.114
+    };
+    let base = (yf * source.width + xf) << 2;
+    let below = base + (source.width << 2);
+    let a = px(base) * (1.0 - xt) + px(base + 4) * xt;
+    let b = px(below) * (1.0 - xt) + px(below + 4) * xt;
+    Some(a * (1.0 - yt) + b * yt)
+}
+
+// total least squares fit of the edge near the segment from a to b, as a point and a direction
+fn fit_side(
+    source: &RGBAImage,
+    a: Point,
+    b: Point,
+    band: f32,
+    profile: &mut Vec<f32>,
+) -> Option<(Point, Point)> {
+    let len = hypotf(b.x - a.x, b.y - a.y);
+    if len < 1.0 {
+        return None;
+    }
+    let dx = (b.x - a.x) / len;
+    let dy = (b.y - a.y) / len;
+    let (nx, ny) = (-dy, dx);
+    let reach = band as isize;
+    let step = (len / MAX_SAMPLES).max(1.0);
+    let end = len * (1.0 - CORNER_MARGIN);
+
+    let mut count = 0;
+    let (mut sw, mut sx, mut sy, mut sxx, mut sxy, mut syy) = (0.0, 0.0, 0.0, 0.0, 0.0, 0.0);
+    let mut t = len * CORNER_MARGIN;
+    'samples: while t < end {
+        let cx = a.x + dx * t;
+        let cy = a.y + dy * t;
+        t += step;
+        profile.clear();
+        for k in -reach - 1..=reach + 1 {
+            let k = k as f32;
+            match luma(source, cx + nx * k, cy + ny * k) {
+                Some(val) => profile.push(val),
+                None => continue 'samples,
+            }
+        }
+        // central differences across the edge
+        let grad = |i: usize| (profile[i + 1] - profile[i - 1]).abs();
+        let mut peak = 2;
+        for i in 3..profile.len() - 2 {
+            if grad(i) > grad(peak) {
+                peak = i;
+            }
+        }
+        let (g0, g1, g2) = (grad(peak - 1), grad(peak), grad(peak + 1));
+        if g1 <= 0.0 {
+            continue;
+        }
+        let denom = g0 - 2.0 * g1 + g2;
+        let offset = if denom < 0.0 {
+            0.5 * (g0 - g2) / denom
+        } else {
+            0.0
+        };
+        let k = (peak as isize - reach - 1) as f32 + offset;
+        let x = cx + nx * k;
+        let y = cy + ny * k;
+        sw += g1;
+        sx += g1 * x;
+        sy += g1 * y;
+        sxx += g1 * x * x;
+        sxy += g1 * x * y;
+        syy += g1 * y * y;
+        count += 1;
+    }
+    if count < MIN_POINTS {
+        return None;
+    }
+    let mx = sx / sw;
+    let my = sy / sw;
+    let cxx = sxx / sw - mx * mx;
+    let cxy = sxy / sw - mx * my;
+    let cyy = syy / sw - my * my;
+    let angle = 0.5 * atan2f(2.0 * cxy, cxx - cyy);
+    Some((
+        Point { x: mx, y: my },
+        Point {
+            x: cosf(angle),
+            y: sinf(angle),
+        },
+    ))
+}
+
+fn intersect(l1: (Point, Point), l2: (Point, Point)) -> Option<Point> {
+    let ((p1, d1), (p2, d2)) = (l1, l2);
+    let cross = d1.x * d2.y - d1.y * d2.x;
+    if cross.abs() < 1e-4 {
+        return None;
+    }
+    let t = ((p2.x - p1.x) * d2.y - (p2.y - p1.y) * d2.x) / cross;
+    Some(Point {
+        x: p1.x + d1.x * t,
+        y: p1.y + d1.y * t,
+    })
+}
+
+// re-fits each side of a coarse quad to the strongest edge within band pixels of it
+pub fn refine(source: &RGBAImage, quad: Quad, band: f32) -> Quad {
+    refine_with(source, quad, band, &mut Vec::new())
+}
+
+// refine with a reused buffer for the profiles across each side
+pub fn refine_with(source: &RGBAImage, quad: Quad, band: f32, profile: &mut Vec<f32>) -> Quad {
+    if band < 1.0 {
+        return quad;
+    }
+    let Quad { a, b, c, d } = quad;
+    let coarse = |p: Point, q: Point| {
+        let len = hypotf(q.x - p.x, q.y - p.y).max(f32::EPSILON);
+        (
+            p,
+            Point {
+                x: (q.x - p.x) / len,
+                y: (q.y - p.y) / len,
+            },
+        )
+    };
+    let mut side =
+        |p: Point, q: Point| fit_side(source, p, q, band, profile).unwrap_or_else(|| coarse(p, q));
+    let ab = side(a, b);
+    let bc = side(b, c);
+    let cd = side(c, d);
+    let da = side(d, a);
+    let max_shift = band * 2.0;
+    let corner = |prev: Point, l1, l2| match intersect(l1, l2) {
+        Some(pt) if hypotf(pt.x - prev.x, pt.y - prev.y) <= max_shift => pt,
+        _ => prev,
+    };
+    Quad {
+        a: corner(a, da, ab),
+        b: corner(b, ab, bc),
+        c: corner(c, bc, cd),
+        d: corner(d, cd, da),
+    }
+}
@@ -1,70 +1,148 @@
-use super::Image;
+use super::{bands, simd, Image, RGBAImage};
 use alloc::vec::Vec;
+use libm::ceilf;
 
 // area-average downscaling
-// TODO: SIMD
+
+// first and last source index, first whole index, and the weights of the partial ends
+type Span = (usize, usize, usize, f32, f32);
+
+// buffers kept between calls
+#[derive(Default)]
+pub struct Scratch {
+    columns: Vec<Span>,
+    band: bands::Scratch,
+}
 
 pub fn downscale(source: &Image, by: f32) -> Image {
-    assert!(by >= 1.0);
-    let &Image {
-        data: ref source,
+    downscale_with(source, by, simd::accumulate)
+}
+
+pub(super) fn downscale_with(
+    source: &Image,
+    by: f32,
+    accumulate: fn(&mut [f32], &[f32], f32),
+) -> Image {
+    let width = source.width;
+    let mut out = Image::default();
+    area_average(
+        width,
+        source.height,
+        by,
+        &mut out,
+        &mut Scratch::default(),
+        |acc, row, weight, _| accumulate(acc, &source.data[row * width..(row + 1) * width], weight),
+    );
+    out
+}
+
+pub fn grayscale_downscale(source: &RGBAImage, by: f32) -> Image {
+    let mut out = Image::default();
+    grayscale_downscale_into(source, by, &mut out, &mut Scratch::default());
+    out
+}
+
+// grayscale each source row as it is needed, so only one full-width row of floats is ever
+// held per band; the result is exactly source.to_grayscale().downscale(by)
+pub fn grayscale_downscale_into(
+    source: &RGBAImage,
+    by: f32,
+    out: &mut Image,
+    scratch: &mut Scratch,
+) {
+    let width = source.width;
+    area_average(
         width,
-        height,
-    } = source;
+        source.height,
+        by,
+        out,
+        scratch,
+        |acc, row, weight, (gray, converted)| {
+            // the last row of one output row is usually the first of the next
+            if *converted != row {
+                gray.resize(width, 0.0);
+                simd::grayscale(
+                    &source.data[(row * width) << 2..((row + 1) * width) << 2],
+                    gray,
+                );
+                *converted = row;
+            }
+            simd::accumulate(acc, gray, weight);
+        },
+    );
+}
+
+// the area is separable: each output row first sums its weighted source rows through add_row,
+// then the columns of that sum are weighted the same way; add_row may keep a converted row,
+// along with its index, in the band's row buffer
+fn area_average(
+    width: usize,
+    height: usize,
+    by: f32,
+    out: &mut Image,
+    scratch: &mut Scratch,
+    add_row: impl Fn(&mut [f32], usize, f32, &mut (Vec<f32>, usize)) + Sync + Send,
+) {
+    // only ever shrinks
+    let by = by.max(1.0);
     let over_by = 1.0 / by;
     let dw = (width as f32 * over_by) as usize;
     let dh = (height as f32 * over_by) as usize;
-    let mut data = Vec::with_capacity(dw * dh);
-    unsafe { data.set_len(data.capacity()) }
+    out.data.resize(dw * dh, 0.0);
+    out.width = dw;
+    out.height = dh;
+    if dw < 3 || dh < 3 {
+        // an image smaller than by is empty
+        if dw > 0 && dh > 0 {
+            tiny(width, height, by, out, scratch, add_row);
+        }
+        return;
+    }
+    let data = &mut out.data;
     let over_by2 = over_by * over_by;
     let mi = dh - 1;
     let mj = dw - 1;
-    for i in 1..mi {
-        let si = i as f32 * by;
-        let sie = si + by;
-        let sif = si as usize;
-        let sic = sif + 1;
-        let sief = sie as usize;
-        let sir = (sic as f32) - si;
-        let sire = sie - (sief as f32);
-        let ib = i * dw;
-        for j in 1..mj {
-            let sj = j as f32 * by;
-            let sje = sj + by;
-            let sjf = sj as usize;
-            let sjc = sjf + 1;
-            let sjef = sje as usize;
-            let sjr = (sjc as f32) - sj;
-            let sjre = sje - (sjef as f32);
-            let mut sum = 0.0;
-            for rsi in sic..sief {
-                for rsj in sjc..sjef {
-                    sum += unsafe { *source.get_unchecked(rsi * width + rsj) };
+    let span = |i: usize| -> Span {
+        let s = i as f32 * by;
+        let e = s + by;
+        let sf = s as usize;
+        let ef = e as usize;
+        (sf, ef, sf + 1, (sf + 1) as f32 - s, e - ef as f32)
+    };
+    scratch.columns.clear();
+    scratch.columns.extend((1..mj).map(span));
+    let columns = &scratch.columns;
+    let cost = (by as usize + 2) * width;
+    bands::rows(
+        data,
+        dw,
+        bands::band_rows(cost),
+        &mut scratch.band,
+        |first, rows, band| {
+            let acc = &mut band.acc;
+            acc.resize(width, 0.0);
+            let mut cached = (core::mem::take(&mut band.row), usize::MAX);
+            for (i, out) in (first..).zip(rows.chunks_exact_mut(dw)) {
+                if i == 0 || i >= mi {
+                    continue;
+                }
+                let (sif, sief, sic, sir, sire) = span(i);
+                acc.fill(0.0);
+                add_row(acc, sif, sir, &mut cached);
+                for rsi in sic..sief {
+                    add_row(acc, rsi, 1.0, &mut cached);
+                }
+                add_row(acc, sief, sire, &mut cached);
+                for (out, &(sjf, sjef, sjc, sjr, sjre)) in out[1..mj].iter_mut().zip(columns.iter())
+                {
+                    let sum =
+                        acc[sjf] * sjr + acc[sjc..sjef].iter().sum::<f32>() + acc[sjef] * sjre;
+                    *out = sum * over_by2;
                 }
             }
-            for rsj in sjc..sjef {
-                sum += unsafe {
-                    *source.get_unchecked(sif * width + rsj) * sir
-                        + *source.get_unchecked(sief * width + rsj) * sire
-                };
-            }
-            for rsi in sic..sief {
-                sum += unsafe {
-                    *source.get_unchecked(rsi * width + sjf) * sjr
-                        + *source.get_unchecked(rsi * width + sjef) * sjre
-                };
-            }
-            unsafe {
-                sum += *source.get_unchecked(sif * width + sjf) * sir * sjr;
-                sum += *source.get_unchecked(sif * width + sjef) * sir * sjre;
-                sum += *source.get_unchecked(sief * width + sjf) * sire * sjr;
-                sum += *source.get_unchecked(sief * width + sjef) * sire * sjre;
-            }
-            unsafe {
-                *data.get_unchecked_mut(ib + j) = sum * over_by2;
-            }
-        }
-    }
+            band.row = cached.0;
+        },
+    );
     for i in 1..mi {
         let ib = i * dw;
         let ibe = ib + mj;
@@ -81,11 +159,39 @@ pub fn downscale(source: &Image, by: f32) -> Image {
             *data.get_unchecked_mut(mibe + j) = *data.get_unchecked(mib + j);
         }
     }
-    Image {
-        data,
-        width: dw,
-        height: dh,
+}
+
+// area_average for outputs without an interior to copy the borders from; every pixel is the plain
+// mean of the source pixels its area touches
+fn tiny(
+    width: usize,
+    height: usize,
+    by: f32,
+    out: &mut Image,
+    scratch: &mut Scratch,
+    add_row: impl Fn(&mut [f32], usize, f32, &mut (Vec<f32>, usize)),
+) {
+    let block = |i: usize, len: usize| {
+        let start = ((i as f32 * by) as usize).min(len - 1);
+        start..(ceilf((i + 1) as f32 * by) as usize).clamp(start + 1, len)
+    };
+    let acc = &mut scratch.band.acc;
+    acc.resize(width, 0.0);
+    let mut cached = (core::mem::take(&mut scratch.band.row), usize::MAX);
+    for (i, out) in out.data.chunks_exact_mut(out.width).enumerate() {
+        let rows = block(i, height);
+        let count = rows.len() as f32;
+        acc.fill(0.0);
+        for row in rows {
+            add_row(acc, row, 1.0, &mut cached);
+        }
+        for (j, out) in out.iter_mut().enumerate() {
+            let columns = block(j, width);
+            let area = count * columns.len() as f32;
+            *out = acc[columns].iter().sum::<f32>() / area;
+        }
     }
+    scratch.band.row = cached.0;
 }
 
 // let over_by = 1.0 / by;
@@ -0,0 +1,156 @@
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use super::{Border, Image, RGBAImage};
+use alloc::vec::Vec;
+
+// darkest illumination that is divided out, so black regions don't blow up into noise
+const MIN_ILLUMINATION: f32 = 0.04;
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Copy)]
+pub struct EnhanceOptions {
+    // shorter side of the illumination map, in pixels
+    pub resolution: usize,
+    // radius of the closing that erases ink, relative to the shorter side of the page
+    pub ink_size: f32,
+    // sigma of the blur over the illumination map, in map pixels
+    pub smoothing: f32,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl EnhanceOptions {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new() -> EnhanceOptions {
+        EnhanceOptions {
+            resolution: 64,
+            ink_size: 0.04,
+            smoothing: 2.0,
+        }
+    }
+}
+
+impl Default for EnhanceOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// running max (dilate) or min (erode) over 2 * radius + 1 pixels along rows, then columns
+fn morph(source: &Image, radius: usize, max: bool) -> Image {
+    let &Image {
+        data: ref source,
+        width,
+        height,
+    } = source;
+    let pick = |a: f32, b: f32| if max { a.max(b) } else { a.min(b) };
+    let mut rows = vec![0.0; source.len()];
+    for y in 0..height {
+        let row = &source[y * width..(y + 1) * width];
+        for x in 0..width {
+            let lo = x.saturating_sub(radius);
+            let hi = (x + radius + 1).min(width);
+            rows[y * width + x] = row[lo..hi].iter().copied().reduce(pick).unwrap();
+        }
+    }
+    let mut data = vec![0.0; source.len()];
+    for y in 0..height {
+        let lo = y.saturating_sub(radius);
+        let hi = (y + radius + 1).min(height);
+        for x in 0..width {
+            data[y * width + x] = (lo..hi)
+                .map(|yy| rows[yy * width + x])
+                .reduce(pick)
+                .unwrap();
+        }
+    }
+    Image {
+        data,
+        width,
+        height,
+    }
+}
+
+// low resolution estimate of the paper under the ink: downscale, close, smooth
+fn illumination(channel: &Image, by: f32, radius: usize, smoothing: f32) -> Image {
+    let mut map = if by > 1.0 {
+        channel.downscale(by)
+    } else {
+        Image {
+            data: channel.data.clone(),
+            width: channel.width,
+            height: channel.height,
+        }
+    };
+    // paper is bright, so dilating removes ink and eroding restores the paper's extent
+    map = morph(&morph(&map, radius, true), radius, false);
+    map.blur(smoothing, Border::Mirror)
+}
+
+// divides out uneven lighting per channel so the paper turns white and ink keeps its color
+pub fn enhance(source: &RGBAImage, options: &EnhanceOptions) -> RGBAImage {
+    let &RGBAImage {
+        data: ref src,
+        width,
+        height,
+    } = source;
+    let side = width.min(height);
+    if side < 2 {
+        return RGBAImage {
+            data: src.clone(),
+            width,
+            height,
+        };
+    }
+    let resolution = options.resolution.max(8);
+    // options may come from JS; past these bounds the map is flat anyway, and NaN means none
+    let ink_size = options.ink_size.clamp(0.0, 1.0);
+    let smoothing = options.smoothing.clamp(0.0, resolution as f32);
+    let by = (side as f32 / resolution as f32).max(1.0);
+    let radius = ((side as f32 * ink_size / by) as usize).max(1);
+    let mut data = src.clone();
+    for c in 0..3 {
+        let channel = Image {
+            data: src
+                .iter()
+                .skip(c)
+                .step_by(4)
+                .map(|&v| v as f32 / 255.0)
+                .collect(),
+            width,
+            height,
+        };
+        let map = illumination(&channel, by, radius, smoothing);
+        // sample the map at pixel centers
+        let scale_x = map.width as f32 / width as f32;
+        let scale_y = map.height as f32 / height as f32;
+        let max_x = (map.width - 1) as f32;
+        let max_y = (map.height - 1) as f32;
+        let xs: Vec<(usize, usize, f32)> = (0..width)
+            .map(|x| {
+                let fx = ((x as f32 + 0.5) * scale_x - 0.5).clamp(0.0, max_x);
+                let x0 = fx as usize;
+                (x0, (x0 + 1).min(map.width - 1), fx - x0 as f32)
+            })
+            .collect();
+        for y in 0..height {
+            let fy = ((y as f32 + 0.5) * scale_y - 0.5).clamp(0.0, max_y);
+            let y0 = fy as usize;
+            let y1 = (y0 + 1).min(map.height - 1);
+            let ty = fy - y0 as f32;
+            let (r0, r1) = (y0 * map.width, y1 * map.width);
+            for (x, &(x0, x1, tx)) in xs.iter().enumerate() {
+                let top = map.data[r0 + x0] * (1.0 - tx) + map.data[r0 + x1] * tx;
+                let bottom = map.data[r1 + x0] * (1.0 - tx) + map.data[r1 + x1] * tx;
+                let light = (top * (1.0 - ty) + bottom * ty).max(MIN_ILLUMINATION);
+                let i = ((y * width + x) << 2) + c;
+                data[i] = (channel.data[y * width + x] / light * 255.0 + 0.5).min(255.0) as u8;
+            }
+        }
+    }
+    RGBAImage {
+        data,
+        width,
+        height,
+    }
+}
@@ -1,100 +1,275 @@
-use super::Image;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use super::{bands, simd, Image};
 use alloc::vec::Vec;
+use libm::{ceilf, expf, sqrtf};
+
+// what the blur sees past the edges of the image
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum Border {
+    // the edge pixel repeats
+    #[default]
+    Clamp,
+    // the image reflects about its edge, repeating the edge pixel
+    Mirror,
+    // everything outside is 0
+    Zero,
+}
+
+// sigma from which the recursive filter is used; below it the kernel is short and exact
+const RECURSIVE_SIGMA: f32 = 4.0;
+
+// columns per strip of the recursive filter's vertical pass
+const STRIP: usize = 128;
+
+// the blur used before detection, close to the 5x5 kernel from Canny's paper
+pub(super) const DETECTION_SIGMA: f32 = 1.4;
 
-// NOTE: empirical tests showed repeated box blur is roughly the same performance
-// I didn't do full tests so it's something to consider for the future if this becomes a bottleneck
+#[inline]
+fn outside(i: isize, len: usize, border: Border) -> Option<usize> {
+    let len = len as isize;
+    if (0..len).contains(&i) {
+        return Some(i as usize);
+    }
+    match border {
+        Border::Clamp => Some(i.clamp(0, len - 1) as usize),
+        Border::Mirror => {
+            let period = len << 1;
+            let m = i.rem_euclid(period);
+            Some((if m >= len { period - 1 - m } else { m }) as usize)
+        }
+        Border::Zero => None,
+    }
+}
 
-pub fn gaussian(source: &Image) -> Image {
+// copies a line of len values into out with pad values on each side, extended per the border
+fn pad_line(at: impl Fn(usize) -> f32, len: usize, pad: usize, border: Border, out: &mut [f32]) {
+    for (k, v) in out.iter_mut().enumerate() {
+        *v = outside(k as isize - pad as isize, len, border).map_or(0.0, &at);
+    }
+}
+
+// normalized taps from -radius to radius
+fn kernel(sigma: f32, taps: &mut Vec<f32>) {
+    let radius = ceilf(sigma * 3.0) as isize;
+    let scale = -0.5 / (sigma * sigma);
+    taps.clear();
+    taps.extend((-radius..=radius).map(|x| expf((x * x) as f32 * scale)));
+    let sum: f32 = taps.iter().sum();
+    for tap in taps.iter_mut() {
+        *tap /= sum;
+    }
+}
+
+// two passes of a 1D kernel; both are row operations so they run on the SIMD accumulator
+fn separable(source: &Image, sigma: f32, border: Border, data: &mut [f32], scratch: &mut Scratch) {
     let &Image {
         data: ref source,
         width,
         height,
     } = source;
-    let mut data = Vec::with_capacity(source.len());
-    unsafe {
-        data.set_len(data.capacity());
-    }
-    let wm = width - 2;
-    let hm = height - 2;
-    let e = 1;
-    let s = width;
-    let sw = s - e;
-    let se = s + e;
-    let e2 = e + e;
-    let s2 = s + s;
-    let sw2 = sw + sw;
-    let ssw = s + sw;
-    let sww = sw - e;
-    let se2 = se + se;
-    let sse = s + se;
-    let see = e + se;
-    for i in 2..hm {
-        let ib = i * width;
-        for j in 2..wm {
-            let bp = ib + j;
-            unsafe {
-                *data.get_unchecked_mut(bp) = (*source.get_unchecked(bp - se2)
-                    + *source.get_unchecked(bp - sw2)
-                    + *source.get_unchecked(bp + sw2)
-                    + *source.get_unchecked(bp + se2))
-                    * 0.01258
-                    + (*source.get_unchecked(bp - sse)
-                        + *source.get_unchecked(bp - ssw)
-                        + *source.get_unchecked(bp - see)
-                        + *source.get_unchecked(bp - sww)
-                        + *source.get_unchecked(bp + sww)
-                        + *source.get_unchecked(bp + see)
-                        + *source.get_unchecked(bp + ssw)
-                        + *source.get_unchecked(bp + sse))
-                        * 0.02516
-                    + (*source.get_unchecked(bp - s2)
-                        + *source.get_unchecked(bp - e2)
-                        + *source.get_unchecked(bp + e2)
-                        + *source.get_unchecked(bp + s2))
-                        * 0.03145
-                    + (*source.get_unchecked(bp - se)
-                        + *source.get_unchecked(bp - sw)
-                        + *source.get_unchecked(bp + sw)
-                        + *source.get_unchecked(bp + se))
-                        * 0.0566
-                    + (*source.get_unchecked(bp - s)
-                        + *source.get_unchecked(bp - e)
-                        + *source.get_unchecked(bp + e)
-                        + *source.get_unchecked(bp + s))
-                        * 0.07547
-                    + *source.get_unchecked(bp) * 0.09434
-            };
+    let Scratch { taps, rows, band } = scratch;
+    kernel(sigma, taps);
+    let taps = &*taps;
+    let radius = taps.len() / 2;
+    let band_rows = bands::band_rows(width * taps.len());
+    rows.clear();
+    rows.resize(source.len(), 0.0);
+    bands::rows(rows, width, band_rows, band, |first, rows, band| {
+        let padded = &mut band.row;
+        padded.resize(width + 2 * radius, 0.0);
+        for (y, dst) in (first..).zip(rows.chunks_exact_mut(width)) {
+            let src = &source[y * width..(y + 1) * width];
+            pad_line(|x| src[x], width, radius, border, padded);
+            for (k, &tap) in taps.iter().enumerate() {
+                simd::accumulate(dst, &padded[k..k + width], tap);
+            }
+        }
+    });
+    let rows = &*rows;
+    data.fill(0.0);
+    bands::rows(data, width, band_rows, &mut (), |first, band, _| {
+        for (y, dst) in (first..).zip(band.chunks_exact_mut(width)) {
+            for (k, &tap) in taps.iter().enumerate() {
+                if let Some(row) = outside((y + k) as isize - radius as isize, height, border) {
+                    simd::accumulate(dst, &rows[row * width..(row + 1) * width], tap);
+                }
+            }
+        }
+    });
+}
+
+// Young and van Vliet's third order recursive approximation, run forward then backward
+struct Recursive {
+    b: f32,
+    a: [f32; 3],
+    pad: usize,
+}
+
+impl Recursive {
+    fn new(sigma: f32) -> Recursive {
+        let q = if sigma >= 2.5 {
+            0.98711 * sigma - 0.9633
+        } else {
+            3.97156 - 4.14554 * sqrtf(1.0 - 0.26891 * sigma)
+        };
+        let (q2, q3) = (q * q, q * q * q);
+        let b0 = 1.57825 + 2.44413 * q + 1.4281 * q2 + 0.422205 * q3;
+        let b1 = 2.44413 * q + 2.85619 * q2 + 1.26661 * q3;
+        let b2 = -(1.4281 * q2 + 1.26661 * q3);
+        let b3 = 0.422205 * q3;
+        Recursive {
+            b: 1.0 - (b1 + b2 + b3) / b0,
+            a: [b1 / b0, b2 / b0, b3 / b0],
+            // far enough that the filter has settled by the time it reaches the image
+            pad: ceilf(sigma * 4.0) as usize,
         }
     }
-    for i in 2..hm {
-        let ib = i * width;
-        let ibe = ib + wm;
-        unsafe {
-            let val = *data.get_unchecked(ib + 2);
-            *data.get_unchecked_mut(ib + 1) = val;
-            *data.get_unchecked_mut(ib) = val;
-            let val = *data.get_unchecked(ibe - 1);
-            *data.get_unchecked_mut(ibe) = val;
-            *data.get_unchecked_mut(ibe + 1) = val;
+
+    // in place over a padded line; each direction starts settled on its first value
+    fn run(&self, line: &mut [f32]) {
+        let [a1, a2, a3] = self.a;
+        let (mut w1, mut w2, mut w3) = (line[0], line[0], line[0]);
+        for v in line.iter_mut() {
+            let w = self.b * *v + a1 * w1 + a2 * w2 + a3 * w3;
+            (w3, w2, w1) = (w2, w1, w);
+            *v = w;
+        }
+        let last = line[line.len() - 1];
+        let (mut w1, mut w2, mut w3) = (last, last, last);
+        for v in line.iter_mut().rev() {
+            let w = self.b * *v + a1 * w1 + a2 * w2 + a3 * w3;
+            (w3, w2, w1) = (w2, w1, w);
+            *v = w;
         }
     }
-    let hmb = hm * width;
-    let hmb2 = hmb - width;
-    let hmbe = hmb + width;
-    let w2 = width + width;
-    for j in 0..width {
-        unsafe {
-            let val = *data.get_unchecked(w2 + j);
-            *data.get_unchecked_mut(width + j) = val;
-            *data.get_unchecked_mut(j) = val;
-            let val = *data.get_unchecked(hmb2 + j);
-            *data.get_unchecked_mut(hmb + j) = val;
-            *data.get_unchecked_mut(hmbe + j) = val;
+
+    // the same over padded columns, a whole row at a time so it stays in cache and vectorizes
+    fn run_rows(&self, rows: &mut [f32], width: usize) {
+        let n = rows.len() / width;
+        let first = rows[..width].to_vec();
+        for r in 0..n {
+            let (done, rest) = rows.split_at_mut(r * width);
+            let row = &mut rest[..width];
+            for v in row.iter_mut() {
+                *v *= self.b;
+            }
+            for (j, &a) in self.a.iter().enumerate() {
+                let prev = match r.checked_sub(j + 1) {
+                    Some(p) => &done[p * width..(p + 1) * width],
+                    None => &first,
+                };
+                simd::accumulate(row, prev, a);
+            }
+        }
+        let last = rows[(n - 1) * width..].to_vec();
+        for r in (0..n).rev() {
+            let (head, done) = rows.split_at_mut((r + 1) * width);
+            let row = &mut head[r * width..];
+            for v in row.iter_mut() {
+                *v *= self.b;
+            }
+            for (j, &a) in self.a.iter().enumerate() {
+                let prev = if r + j + 1 < n {
+                    &done[j * width..(j + 1) * width]
+                } else {
+                    &last
+                };
+                simd::accumulate(row, prev, a);
+            }
         }
     }
-    Image {
-        data,
+}
+
+// the same cost per pixel for any sigma
+fn recursive(source: &Image, sigma: f32, border: Border, data: &mut [f32], scratch: &mut Scratch) {
+    let &Image {
+        data: ref source,
         width,
         height,
+    } = source;
+    let filter = Recursive::new(sigma);
+    let pad = filter.pad;
+    bands::rows(
+        data,
+        width,
+        bands::band_rows(width),
+        &mut scratch.band,
+        |first, band, scratch| {
+            let line = &mut scratch.row;
+            line.resize(width + 2 * pad, 0.0);
+            for (y, dst) in (first..).zip(band.chunks_exact_mut(width)) {
+                let src = &source[y * width..(y + 1) * width];
+                pad_line(|x| src[x], width, pad, border, line);
+                filter.run(line);
+                dst.copy_from_slice(&line[pad..pad + width]);
+            }
+        },
+    );
+    // columns go through in strips, each a narrow padded copy of the rows
+    let strips = bands::map(width.div_ceil(STRIP), |s| {
+        let (x, w) = (s * STRIP, STRIP.min(width - s * STRIP));
+        let mut rows = vec![0.0; (height + 2 * pad) * w];
+        for (y, dst) in rows.chunks_exact_mut(w).enumerate() {
+            if let Some(row) = outside(y as isize - pad as isize, height, border) {
+                dst.copy_from_slice(&data[row * width + x..row * width + x + w]);
+            }
+        }
+        filter.run_rows(&mut rows, w);
+        rows
+    });
+    bands::rows(
+        data,
+        width,
+        bands::band_rows(width),
+        &mut (),
+        |first, band, _| {
+            for (y, dst) in (first..).zip(band.chunks_exact_mut(width)) {
+                for (dst, strip) in dst.chunks_mut(STRIP).zip(strips.iter()) {
+                    let w = dst.len();
+                    dst.copy_from_slice(&strip[(y + pad) * w..(y + pad + 1) * w]);
+                }
+            }
+        },
+    );
+}
+
+// buffers kept between calls
+#[derive(Default)]
+pub struct Scratch {
+    taps: Vec<f32>,
+    rows: Vec<f32>,
+    band: bands::Scratch,
+}
+
+pub fn gaussian(source: &Image, sigma: f32, border: Border) -> Image {
+    let mut out = Image::default();
+    gaussian_into(source, sigma, border, &mut out, &mut Scratch::default());
+    out
+}
+
+// gaussian into an image whose buffer is reused
+pub fn gaussian_into(
+    source: &Image,
+    sigma: f32,
+    border: Border,
+    out: &mut Image,
+    scratch: &mut Scratch,
+) {
+    out.data.resize(source.data.len(), 0.0);
+    out.width = source.width;
+    out.height = source.height;
+    if !(sigma > 0.0 && sigma.is_finite()) || source.data.is_empty() {
+        out.data.copy_from_slice(&source.data);
+        return;
+    }
+    // a sigma past the image's size has flattened it already, and this keeps the padding bounded
+    let sigma = sigma.min(source.width.max(source.height) as f32);
+    if sigma < RECURSIVE_SIGMA {
+        separable(source, sigma, border, &mut out.data, scratch);
+    } else {
+        recursive(source, sigma, border, &mut out.data, scratch);
     }
 }
@@ -1,24 +1,35 @@
-use super::{Image, RGBAImage};
+use super::{bands, simd, Image, RGBAImage};
 
-// grayscale and fit range to 0-1
-// TODO: SIMD
+// red, green and blue weights that grayscale and fit range to 0-1
+pub(super) const WEIGHTS: [f32; 3] = [0.0011679687, 0.0022929688, 0.0004453125];
+
+// same weights for an RGB(A) sample with channels from 0 to 255
+#[inline]
+pub(super) fn luminance(px: [f32; 4]) -> f32 {
+    px[0] * WEIGHTS[0] + px[1] * WEIGHTS[1] + px[2] * WEIGHTS[2]
+}
 
 pub fn grayscale(source: &RGBAImage) -> Image {
+    let mut out = Image::default();
+    grayscale_into(source, &mut out);
+    out
+}
+
+// grayscale into an image whose buffer is reused
+pub fn grayscale_into(source: &RGBAImage, out: &mut Image) {
     let &RGBAImage {
         data: ref source,
         width,
         height,
     } = source;
-    Image {
-        data: source
-            .chunks_exact(4)
-            .map(|rgba| unsafe {
-                (*rgba.get_unchecked(0) as f32) * 0.0011679687
-                    + (*rgba.get_unchecked(1) as f32) * 0.0022929688
-                    + (*rgba.get_unchecked(2) as f32) * 0.0004453125
-            })
-            .collect(),
+    out.data.resize(width * height, 0.0);
+    out.width = width;
+    out.height = height;
+    bands::rows(
+        &mut out.data,
         width,
-        height,
-    }
+        bands::band_rows(width),
+        &mut (),
+        |y, rows, _| simd::grayscale(&source[(y * width) << 2..], rows),
+    );
 }
@@ -1,11 +1,28 @@
+use super::codec::{self, EncodeError, JpegOptions, PngOptions};
 use alloc::vec::Vec;
 
+mod bands;
+mod binarize;
+mod color;
 mod document;
 mod downscale;
+mod enhance;
 mod gaussian;
 mod grayscale;
-pub use document::{GradientVotesResult, Line, Point, Quad, ScoredQuad};
+mod orientation;
+mod rotate;
+mod simd;
+pub use binarize::{BinarizeOptions, Threshold};
+pub use color::ColorOptions;
+pub use document::{
+    aspect_ratio, snap_aspect, DeskewOptions, DetectOptions, ExtractOptions, Fill,
+    GradientVotesResult, Interpolation, Line, PaperSize, Point, Quad, ScoredQuad, WarpOptions,
+};
+pub use enhance::EnhanceOptions;
+pub use gaussian::Border;
+pub use orientation::Orientation;
 
+#[derive(Default)]
 pub struct Image {
     pub data: Vec<f32>,
     pub width: usize,
@@ -16,8 +33,41 @@ impl Image {
     pub fn downscale(&self, by: f32) -> Image {
         downscale::downscale(self, by)
     }
+    // the light blur that detection runs on
     pub fn gaussian(&self) -> Image {
-        gaussian::gaussian(self)
+        gaussian::gaussian(self, gaussian::DETECTION_SIGMA, Border::Clamp)
+    }
+    // gaussian blur of any sigma; large ones cost no more per pixel than small ones
+    pub fn blur(&self, sigma: f32, border: Border) -> Image {
+        gaussian::gaussian(self, sigma, border)
+    }
+    pub fn to_jpeg(&self, quality: u8) -> Result<Vec<u8>, EncodeError> {
+        codec::encode_jpeg_gray(self, quality)
+    }
+    pub fn binarize(&self, options: &BinarizeOptions) -> GrayImage {
+        binarize::binarize(self, options)
+    }
+    pub fn binarize_bits(&self, options: &BinarizeOptions) -> BitImage {
+        binarize::binarize_bits(self, options)
+    }
+    // rotation that makes the text on a page upright, from line direction and ascender shapes
+    pub fn orientation(&self) -> Orientation {
+        orientation::orientation(self)
+    }
+    // quantizes values from 0 to 1 into 8 bits
+    pub fn to_gray8(&self) -> GrayImage {
+        GrayImage {
+            data: self
+                .data
+                .iter()
+                .map(|&v| (v.clamp(0.0, 1.0) * 255.0 + 0.5) as u8)
+                .collect(),
+            width: self.width,
+            height: self.height,
+        }
+    }
+    pub fn to_png(&self, compression: u8) -> Result<Vec<u8>, EncodeError> {
+        codec::encode_png_gray(&self.to_gray8(), compression)
     }
     // pub fn edges(&self, threshold: f32) -> Vec<Line> {
     //     let result = document::gradient_votes(self);
@@ -25,15 +75,121 @@ impl Image {
     //     edges.sort_unstable_by(|a, b| b.cmp(a));
     //     edges
     // }
-    pub fn document(&self) -> Option<ScoredQuad> {
-        let result = document::gradient_votes(self);
-        let mut edges = document::edges(&result, 0.05);
-        edges.truncate(20);
+    pub fn documents(&self, options: &DetectOptions) -> Vec<ScoredQuad> {
+        let result = document::gradient_votes(self, options);
+        let mut edges = document::edges(&result, options);
+        edges.truncate(options.max_lines);
         edges.sort_unstable_by(|a, b| b.cmp(a));
-        document::documents(&result, &edges).get(0).copied()
+        document::documents(&result, &edges, options)
+    }
+    pub fn documents_near(
+        &self,
+        options: &DetectOptions,
+        prior: Quad,
+        max_dist: f32,
+        max_ang_err: u8,
+    ) -> Vec<ScoredQuad> {
+        let result = document::gradient_votes(self, options);
+        let mut edges = document::edges_near(&result, options, prior, max_dist, max_ang_err);
+        edges.truncate(options.max_lines);
+        edges.sort_unstable_by(|a, b| b.cmp(a));
+        document::documents(&result, &edges, options)
+    }
+    pub fn document(&self, options: &DetectOptions) -> Option<ScoredQuad> {
+        self.documents(options).first().copied()
+    }
+    pub fn separate_documents(&self, options: &DetectOptions, max: usize) -> Vec<ScoredQuad> {
+        // small documents are expected here, so confidence only drops below a much smaller area
+        let options = &DetectOptions {
+            min_area: options.multi_min_area,
+            ..*options
+        };
+        document::separate(
+            &self.documents(options),
+            options.max_overlap,
+            options.min_confidence,
+            max,
+        )
+    }
+}
+
+// buffers kept from frame to frame by a Detector; they only grow when the frame does
+#[derive(Default)]
+pub struct Workspace {
+    gray: Image,
+    blurred: Image,
+    downscale: downscale::Scratch,
+    blur: gaussian::Scratch,
+    votes: GradientVotesResult,
+    lines: Vec<Line>,
+    quads: Vec<ScoredQuad>,
+    profile: Vec<f32>,
+}
+
+impl Workspace {
+    // grayscale, downscale by by and blur a frame for detection
+    pub fn prepare(&mut self, rgba: &RGBAImage, by: f32) {
+        if by != 1.0 {
+            downscale::grayscale_downscale_into(rgba, by, &mut self.gray, &mut self.downscale);
+        } else {
+            grayscale::grayscale_into(rgba, &mut self.gray);
+        }
+        gaussian::gaussian_into(
+            &self.gray,
+            gaussian::DETECTION_SIGMA,
+            Border::Clamp,
+            &mut self.blurred,
+            &mut self.blur,
+        );
+    }
+    // width and height of the prepared frame
+    pub fn size(&self) -> (usize, usize) {
+        (self.blurred.width, self.blurred.height)
+    }
+    // Image::documents on the prepared frame
+    pub fn documents(&mut self, options: &DetectOptions) -> &[ScoredQuad] {
+        self.vote(options);
+        self.quads(options)
+    }
+    // gradient votes for the prepared frame, which quads and quads_near then share
+    pub fn vote(&mut self, options: &DetectOptions) {
+        document::gradient_votes_into(&self.blurred, options, &mut self.votes);
+    }
+    // quads from every edge in the votes
+    pub fn quads(&mut self, options: &DetectOptions) -> &[ScoredQuad] {
+        document::edges_into(&self.votes, options, &mut self.lines);
+        self.combine(options)
+    }
+    // quads from the edges in the votes close to the sides of prior, as Image::documents_near
+    pub fn quads_near(
+        &mut self,
+        options: &DetectOptions,
+        prior: Quad,
+        max_dist: f32,
+        max_ang_err: u8,
+    ) -> &[ScoredQuad] {
+        document::edges_near_into(
+            &self.votes,
+            options,
+            prior,
+            max_dist,
+            max_ang_err,
+            &mut self.lines,
+        );
+        self.combine(options)
+    }
+    fn combine(&mut self, options: &DetectOptions) -> &[ScoredQuad] {
+        self.lines.truncate(options.max_lines);
+        self.lines.sort_unstable_by(|a, b| b.cmp(a));
+        document::documents_into(&self.votes, &self.lines, options, &mut self.quads);
+        &self.quads
+    }
+    pub fn refine(&mut self, rgba: &RGBAImage, quad: Quad, band: f32) -> Quad {
+        document::refine_with(rgba, quad, band, &mut self.profile)
     }
 }
 
+#[derive(Default)]
 pub struct RGBAImage {
     pub data: Vec<u8>,
     pub width: usize,
@@ -44,7 +200,97 @@ impl RGBAImage {
     pub fn to_grayscale(&self) -> Image {
         grayscale::grayscale(self)
     }
-    pub fn perspective(&self, quad: Quad, width: usize, height: usize) -> RGBAImage {
-        document::perspective(self, quad, width, height)
+    // same as to_grayscale().downscale(by) without the full resolution copy
+    pub fn to_grayscale_downscaled(&self, by: f32) -> Image {
+        downscale::grayscale_downscale(self, by)
+    }
+    pub fn perspective(
+        &self,
+        quad: Quad,
+        width: usize,
+        height: usize,
+        options: &WarpOptions,
+    ) -> RGBAImage {
+        document::perspective(self, quad, width, height, options)
+    }
+    pub fn perspective_gray(
+        &self,
+        quad: Quad,
+        width: usize,
+        height: usize,
+        options: &WarpOptions,
+    ) -> Image {
+        document::perspective_gray(self, quad, width, height, options)
+    }
+    pub fn perspective_gray8(
+        &self,
+        quad: Quad,
+        width: usize,
+        height: usize,
+        options: &WarpOptions,
+    ) -> GrayImage {
+        document::perspective_gray8(self, quad, width, height, options)
+    }
+    // luminance below the threshold (0-1) becomes ink
+    pub fn perspective_bits(
+        &self,
+        quad: Quad,
+        width: usize,
+        height: usize,
+        options: &WarpOptions,
+        threshold: f32,
+    ) -> BitImage {
+        document::perspective_bits(self, quad, width, height, options, threshold)
+    }
+    pub fn refine(&self, quad: Quad, band: f32) -> Quad {
+        document::refine(self, quad, band)
+    }
+    // clockwise rotation of the content in degrees, or 0 if none is found within the limit
+    pub fn skew_angle(&self, options: &DeskewOptions) -> f32 {
+        document::skew_angle(self, options)
+    }
+    pub fn deskew(&self, options: &DeskewOptions, warp: &WarpOptions) -> RGBAImage {
+        document::deskew(self, options, warp)
+    }
+    pub fn correct_color(&self, options: &ColorOptions) -> RGBAImage {
+        color::correct_color(self, options)
+    }
+    pub fn enhance(&self, options: &EnhanceOptions) -> RGBAImage {
+        enhance::enhance(self, options)
+    }
+    pub fn rotate(&self, turns: u8, mirror: bool) -> RGBAImage {
+        rotate::rotate(self, turns, mirror)
+    }
+    pub fn to_jpeg(&self, options: &JpegOptions) -> Result<Vec<u8>, EncodeError> {
+        codec::encode_jpeg(self, options)
+    }
+    pub fn to_png(&self, options: &PngOptions) -> Result<Vec<u8>, EncodeError> {
+        codec::encode_png(self, options)
+    }
+}
+
+pub struct GrayImage {
+    pub data: Vec<u8>,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl GrayImage {
+    pub fn to_png(&self, compression: u8) -> Result<Vec<u8>, EncodeError> {
+        codec::encode_png_gray(self, compression)
+    }
+}
+
+// one bit per pixel, set for white, packed from the most significant bit with each row
+// starting on a new byte
+pub struct BitImage {
+    pub data: Vec<u8>,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl BitImage {
+    pub fn to_png(&self, compression: u8) -> Result<Vec<u8>, EncodeError> {
+        codec::encode_png_bits(self, compression)
     }
 }
@@ -0,0 +1,129 @@
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use super::{BinarizeOptions, Image};
+use alloc::vec::Vec;
+
+// shorter side the page is downscaled to before classifying
+const RESOLUTION: f32 = 1000.0;
+// fraction of a text line's peak ink that counts as its x-height core
+const CORE: f32 = 0.5;
+// profiles are taken over this many strips so slightly skewed lines don't smear together
+const STRIPS: usize = 8;
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Copy)]
+pub struct Orientation {
+    // quarter turns clockwise that make the text upright
+    pub turns: u8,
+    // 0-1 from how clearly the text runs one way and how lopsided its lines are
+    pub confidence: f32,
+}
+
+// spread of the profile relative to its mean; rows across text lines swing between gaps and
+// ink, while rows along them see about the same amount of ink everywhere
+fn structure(profile: &[f32]) -> f32 {
+    let mean = profile.iter().sum::<f32>() / profile.len() as f32;
+    if mean == 0.0 {
+        return 0.0;
+    }
+    profile.iter().map(|v| (v - mean) * (v - mean)).sum::<f32>()
+        / (profile.len() as f32 * mean * mean)
+}
+
+// ink before each line's core (ascenders when upright) and ink after it (descenders)
+fn lopsidedness(profile: &[f32]) -> (f32, f32) {
+    let max = profile.iter().copied().fold(0.0, f32::max);
+    let gap = max * 0.05;
+    let (mut before, mut after) = (0.0, 0.0);
+    let mut i = 0;
+    while i < profile.len() {
+        if profile[i] <= gap {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < profile.len() && profile[i] > gap {
+            i += 1;
+        }
+        let line = &profile[start..i];
+        let peak = line.iter().copied().fold(0.0, f32::max);
+        let first = line.iter().position(|&v| v >= peak * CORE).unwrap();
+        let last = line.iter().rposition(|&v| v >= peak * CORE).unwrap();
+        before += line[..first].iter().sum::<f32>();
+        after += line[last + 1..].iter().sum::<f32>();
+    }
+    (before, after)
+}
+
+// structure averaged over the strips, and lopsidedness over all of them from -1 to 1
+fn measure(profiles: &[Vec<f32>]) -> (f32, f32) {
+    let structure = profiles.iter().map(|p| structure(p)).sum::<f32>() / profiles.len() as f32;
+    let (before, after) = profiles
+        .iter()
+        .map(|p| lopsidedness(p))
+        .fold((0.0, 0.0), |(b, a), (pb, pa)| (b + pb, a + pa));
+    let lopsided = if before + after == 0.0 {
+        0.0
+    } else {
+        (before - after) / (before + after)
+    };
+    (structure, lopsided)
+}
+
+pub fn orientation(source: &Image) -> Orientation {
+    if source.width == 0 || source.height == 0 {
+        return Orientation {
+            turns: 0,
+            confidence: 0.0,
+        };
+    }
+    let side = source.width.min(source.height) as f32;
+    let small;
+    let page = if side > RESOLUTION {
+        small = source.downscale(side / RESOLUTION);
+        &small
+    } else {
+        source
+    };
+    let (width, height) = (page.width, page.height);
+    let ink = page.binarize(&BinarizeOptions::new());
+    let mut rows = vec![vec![0.0; height]; STRIPS];
+    let mut columns = vec![vec![0.0; width]; STRIPS];
+    for (y, row) in ink.data.chunks_exact(width).enumerate() {
+        for (x, &v) in row.iter().enumerate() {
+            if v == 0 {
+                rows[x * STRIPS / width][y] += 1.0;
+                columns[y * STRIPS / height][x] += 1.0;
+            }
+        }
+    }
+    // lines that run into the page border are cut off, so their shape says nothing
+    for profile in rows.iter_mut().chain(columns.iter_mut()) {
+        let margin = profile.len() / 50;
+        let len = profile.len();
+        profile[..margin].fill(0.0);
+        profile[len - margin..].fill(0.0);
+    }
+
+    let (across_rows, row_lopsided) = measure(&rows);
+    let (across_columns, column_lopsided) = measure(&columns);
+    let total = across_rows + across_columns;
+    if total == 0.0 {
+        return Orientation {
+            turns: 0,
+            confidence: 0.0,
+        };
+    }
+    let direction = (across_rows - across_columns) / total;
+    let (turns, lopsided) = if direction >= 0.0 {
+        (if row_lopsided >= 0.0 { 0 } else { 2 }, row_lopsided)
+    } else {
+        // tops of sideways text point left when the page was turned counterclockwise
+        (if column_lopsided >= 0.0 { 1 } else { 3 }, column_lopsided)
+    };
+    Orientation {
+        turns,
+        confidence: direction.abs().min(lopsided.abs()),
+    }
+}
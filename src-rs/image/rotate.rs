@@ -0,0 +1,37 @@
+use super::RGBAImage;
+use alloc::vec::Vec;
+
+// mirrors horizontally if asked, then rotates by quarter turns clockwise
+pub fn rotate(source: &RGBAImage, turns: u8, mirror: bool) -> RGBAImage {
+    let &RGBAImage {
+        data: ref source,
+        width,
+        height,
+    } = source;
+    let turns = turns & 3;
+    let (dw, dh) = if turns & 1 == 1 {
+        (height, width)
+    } else {
+        (width, height)
+    };
+    let mut data = Vec::with_capacity(source.len());
+    for y in 0..dh {
+        for x in 0..dw {
+            // undo the rotation, then the mirror
+            let (sx, sy) = match turns {
+                0 => (x, y),
+                1 => (y, height - 1 - x),
+                2 => (width - 1 - x, height - 1 - y),
+                _ => (width - 1 - y, x),
+            };
+            let sx = if mirror { width - 1 - sx } else { sx };
+            let base = (sy * width + sx) << 2;
+            data.extend_from_slice(&source[base..base + 4]);
+        }
+    }
+    RGBAImage {
+        data,
+        width: dw,
+        height: dh,
+    }
+}
@@ -0,0 +1,159 @@
+// row kernels for the passes over full camera frames, with core::arch versions where they help;
+// every version does the same float operations in the same order, so results are identical
+
+mod scalar;
+
+#[cfg(target_arch = "aarch64")]
+mod neon;
+#[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+mod simd128;
+#[cfg(target_arch = "x86_64")]
+mod x86;
+
+// luminance of each RGBA pixel in src, written to dst
+#[cfg(target_arch = "x86_64")]
+pub fn grayscale(src: &[u8], dst: &mut [f32]) {
+    assert!(src.len() >= dst.len() * 4);
+    if x86::has_avx2() {
+        unsafe { x86::grayscale_avx2(src, dst) }
+    } else {
+        unsafe { x86::grayscale_sse2(src, dst) }
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+pub fn grayscale(src: &[u8], dst: &mut [f32]) {
+    assert!(src.len() >= dst.len() * 4);
+    unsafe { neon::grayscale(src, dst) }
+}
+
+#[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+pub fn grayscale(src: &[u8], dst: &mut [f32]) {
+    assert!(src.len() >= dst.len() * 4);
+    unsafe { simd128::grayscale(src, dst) }
+}
+
+#[cfg(not(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    all(target_arch = "wasm32", target_feature = "simd128")
+)))]
+pub use scalar::grayscale;
+
+// acc += row * weight, elementwise
+#[cfg(target_arch = "x86_64")]
+pub fn accumulate(acc: &mut [f32], row: &[f32], weight: f32) {
+    assert!(row.len() >= acc.len());
+    if x86::has_avx2() {
+        unsafe { x86::accumulate_avx2(acc, row, weight) }
+    } else {
+        unsafe { x86::accumulate_sse2(acc, row, weight) }
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+pub fn accumulate(acc: &mut [f32], row: &[f32], weight: f32) {
+    assert!(row.len() >= acc.len());
+    unsafe { neon::accumulate(acc, row, weight) }
+}
+
+#[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+pub fn accumulate(acc: &mut [f32], row: &[f32], weight: f32) {
+    assert!(row.len() >= acc.len());
+    unsafe { simd128::accumulate(acc, row, weight) }
+}
+
+#[cfg(not(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    all(target_arch = "wasm32", target_feature = "simd128")
+)))]
+pub use scalar::accumulate;
+
+#[cfg(test)]
+mod tests {
+    use super::scalar;
+    use alloc::vec::Vec;
+
+    fn noise(len: usize, seed: u32) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+                (state >> 24) as u8
+            })
+            .collect()
+    }
+
+    fn floats(len: usize, seed: u32) -> Vec<f32> {
+        noise(len, seed)
+            .into_iter()
+            .map(|v| v as f32 / 97.0 - 1.0)
+            .collect()
+    }
+
+    // lengths around every vector width, so both the vector loop and the tail get covered
+    const LENGTHS: [usize; 12] = [0, 1, 3, 4, 5, 7, 8, 9, 15, 16, 17, 1001];
+
+    fn check_grayscale(kernel: fn(&[u8], &mut [f32])) {
+        for (seed, &len) in LENGTHS.iter().enumerate() {
+            let src = noise(len * 4, seed as u32);
+            let mut expected = vec![0.0; len];
+            let mut actual = vec![0.0; len];
+            scalar::grayscale(&src, &mut expected);
+            kernel(&src, &mut actual);
+            assert_eq!(expected, actual, "grayscale of {len} pixels");
+        }
+    }
+
+    fn check_accumulate(kernel: fn(&mut [f32], &[f32], f32)) {
+        for (seed, &len) in LENGTHS.iter().enumerate() {
+            let row = floats(len, seed as u32);
+            let mut expected = floats(len, seed as u32 + 100);
+            let mut actual = expected.clone();
+            for weight in [1.0, 0.375, -2.5] {
+                scalar::accumulate(&mut expected, &row, weight);
+                kernel(&mut actual, &row, weight);
+            }
+            assert_eq!(expected, actual, "accumulate of {len} values");
+        }
+    }
+
+    #[test]
+    fn grayscale_matches_scalar() {
+        check_grayscale(super::grayscale);
+    }
+
+    #[test]
+    fn accumulate_matches_scalar() {
+        check_accumulate(super::accumulate);
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn x86_matches_scalar() {
+        use super::x86;
+        check_grayscale(|src, dst| unsafe { x86::grayscale_sse2(src, dst) });
+        check_accumulate(|acc, row, weight| unsafe { x86::accumulate_sse2(acc, row, weight) });
+        if x86::has_avx2() {
+            check_grayscale(|src, dst| unsafe { x86::grayscale_avx2(src, dst) });
+            check_accumulate(|acc, row, weight| unsafe { x86::accumulate_avx2(acc, row, weight) });
+        }
+    }
+
+    #[test]
+    fn downscale_matches_scalar() {
+        use super::super::{downscale::downscale_with, Image};
+        let (width, height) = (203, 157);
+        let source = Image {
+            data: floats(width * height, 7),
+            width,
+            height,
+        };
+        for by in [1.0, 1.5, 2.0, 3.7, 8.0] {
+            let expected = downscale_with(&source, by, scalar::accumulate);
+            let actual = downscale_with(&source, by, super::accumulate);
+            assert_eq!(expected.data, actual.data, "downscale by {by}");
+        }
+    }
+}
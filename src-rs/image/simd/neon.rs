@@ -0,0 +1,45 @@
+use super::{super::grayscale::WEIGHTS, scalar};
+use core::arch::aarch64::*;
+
+// NEON is part of aarch64, so there is nothing to detect
+
+// pixels are loaded as little-endian u32 lanes, so each channel is a shift and a mask away
+#[target_feature(enable = "neon")]
+pub unsafe fn grayscale(src: &[u8], dst: &mut [f32]) {
+    let mask = vdupq_n_u32(0xFF);
+    let (wr, wg, wb) = (
+        vdupq_n_f32(WEIGHTS[0]),
+        vdupq_n_f32(WEIGHTS[1]),
+        vdupq_n_f32(WEIGHTS[2]),
+    );
+    let end = dst.len() & !3;
+    let mut i = 0;
+    while i < end {
+        let px = vreinterpretq_u32_u8(vld1q_u8(src.as_ptr().add(i << 2)));
+        let r = vcvtq_f32_u32(vandq_u32(px, mask));
+        let g = vcvtq_f32_u32(vandq_u32(vshrq_n_u32::<8>(px), mask));
+        let b = vcvtq_f32_u32(vandq_u32(vshrq_n_u32::<16>(px), mask));
+        // separate multiplies and adds; a fused multiply-add would round differently
+        let y = vaddq_f32(
+            vaddq_f32(vmulq_f32(r, wr), vmulq_f32(g, wg)),
+            vmulq_f32(b, wb),
+        );
+        vst1q_f32(dst.as_mut_ptr().add(i), y);
+        i += 4;
+    }
+    scalar::grayscale(&src[end << 2..], &mut dst[end..]);
+}
+
+#[target_feature(enable = "neon")]
+pub unsafe fn accumulate(acc: &mut [f32], row: &[f32], weight: f32) {
+    let w = vdupq_n_f32(weight);
+    let end = acc.len() & !3;
+    let mut i = 0;
+    while i < end {
+        let a = acc.as_mut_ptr().add(i);
+        let v = vmulq_f32(vld1q_f32(row.as_ptr().add(i)), w);
+        vst1q_f32(a, vaddq_f32(vld1q_f32(a), v));
+        i += 4;
+    }
+    scalar::accumulate(&mut acc[end..], &row[end..], weight);
+}
@@ -0,0 +1,13 @@
+use super::super::grayscale::WEIGHTS;
+
+pub fn grayscale(src: &[u8], dst: &mut [f32]) {
+    for (px, out) in src.chunks_exact(4).zip(dst) {
+        *out = px[0] as f32 * WEIGHTS[0] + px[1] as f32 * WEIGHTS[1] + px[2] as f32 * WEIGHTS[2];
+    }
+}
+
+pub fn accumulate(acc: &mut [f32], row: &[f32], weight: f32) {
+    for (a, &v) in acc.iter_mut().zip(row) {
+        *a += v * weight;
+    }
+}
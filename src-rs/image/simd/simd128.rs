@@ -0,0 +1,42 @@
+use super::{super::grayscale::WEIGHTS, scalar};
+use core::arch::wasm32::*;
+
+// wasm has no runtime detection, so this is only built with -C target-feature=+simd128
+
+// pixels are loaded as little-endian u32 lanes, so each channel is a shift and a mask away
+pub unsafe fn grayscale(src: &[u8], dst: &mut [f32]) {
+    let mask = u32x4_splat(0xFF);
+    let (wr, wg, wb) = (
+        f32x4_splat(WEIGHTS[0]),
+        f32x4_splat(WEIGHTS[1]),
+        f32x4_splat(WEIGHTS[2]),
+    );
+    let end = dst.len() & !3;
+    let mut i = 0;
+    while i < end {
+        let px = v128_load(src.as_ptr().add(i << 2) as *const v128);
+        let r = f32x4_convert_u32x4(v128_and(px, mask));
+        let g = f32x4_convert_u32x4(v128_and(u32x4_shr(px, 8), mask));
+        let b = f32x4_convert_u32x4(v128_and(u32x4_shr(px, 16), mask));
+        let y = f32x4_add(
+            f32x4_add(f32x4_mul(r, wr), f32x4_mul(g, wg)),
+            f32x4_mul(b, wb),
+        );
+        v128_store(dst.as_mut_ptr().add(i) as *mut v128, y);
+        i += 4;
+    }
+    scalar::grayscale(&src[end << 2..], &mut dst[end..]);
+}
+
+pub unsafe fn accumulate(acc: &mut [f32], row: &[f32], weight: f32) {
+    let w = f32x4_splat(weight);
+    let end = acc.len() & !3;
+    let mut i = 0;
+    while i < end {
+        let a = acc.as_mut_ptr().add(i) as *mut v128;
+        let v = f32x4_mul(v128_load(row.as_ptr().add(i) as *const v128), w);
+        v128_store(a, f32x4_add(v128_load(a), v));
+        i += 4;
+    }
+    scalar::accumulate(&mut acc[end..], &row[end..], weight);
+}
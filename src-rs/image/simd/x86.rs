@@ -0,0 +1,92 @@
+use super::{super::grayscale::WEIGHTS, scalar};
+use core::arch::x86_64::*;
+
+// SSE2 is part of x86_64; AVX2 is detected at runtime when std is available
+#[inline]
+pub fn has_avx2() -> bool {
+    #[cfg(feature = "std")]
+    {
+        std::is_x86_feature_detected!("avx2")
+    }
+    #[cfg(not(feature = "std"))]
+    {
+        cfg!(target_feature = "avx2")
+    }
+}
+
+// pixels are loaded as little-endian u32 lanes, so each channel is a shift and a mask away
+pub unsafe fn grayscale_sse2(src: &[u8], dst: &mut [f32]) {
+    let mask = _mm_set1_epi32(0xFF);
+    let (wr, wg, wb) = (
+        _mm_set1_ps(WEIGHTS[0]),
+        _mm_set1_ps(WEIGHTS[1]),
+        _mm_set1_ps(WEIGHTS[2]),
+    );
+    let end = dst.len() & !3;
+    let mut i = 0;
+    while i < end {
+        let px = _mm_loadu_si128(src.as_ptr().add(i << 2) as *const __m128i);
+        let r = _mm_cvtepi32_ps(_mm_and_si128(px, mask));
+        let g = _mm_cvtepi32_ps(_mm_and_si128(_mm_srli_epi32::<8>(px), mask));
+        let b = _mm_cvtepi32_ps(_mm_and_si128(_mm_srli_epi32::<16>(px), mask));
+        let y = _mm_add_ps(
+            _mm_add_ps(_mm_mul_ps(r, wr), _mm_mul_ps(g, wg)),
+            _mm_mul_ps(b, wb),
+        );
+        _mm_storeu_ps(dst.as_mut_ptr().add(i), y);
+        i += 4;
+    }
+    scalar::grayscale(&src[end << 2..], &mut dst[end..]);
+}
+
+#[target_feature(enable = "avx2")]
+pub unsafe fn grayscale_avx2(src: &[u8], dst: &mut [f32]) {
+    let mask = _mm256_set1_epi32(0xFF);
+    let (wr, wg, wb) = (
+        _mm256_set1_ps(WEIGHTS[0]),
+        _mm256_set1_ps(WEIGHTS[1]),
+        _mm256_set1_ps(WEIGHTS[2]),
+    );
+    let end = dst.len() & !7;
+    let mut i = 0;
+    while i < end {
+        let px = _mm256_loadu_si256(src.as_ptr().add(i << 2) as *const __m256i);
+        let r = _mm256_cvtepi32_ps(_mm256_and_si256(px, mask));
+        let g = _mm256_cvtepi32_ps(_mm256_and_si256(_mm256_srli_epi32::<8>(px), mask));
+        let b = _mm256_cvtepi32_ps(_mm256_and_si256(_mm256_srli_epi32::<16>(px), mask));
+        let y = _mm256_add_ps(
+            _mm256_add_ps(_mm256_mul_ps(r, wr), _mm256_mul_ps(g, wg)),
+            _mm256_mul_ps(b, wb),
+        );
+        _mm256_storeu_ps(dst.as_mut_ptr().add(i), y);
+        i += 8;
+    }
+    scalar::grayscale(&src[end << 2..], &mut dst[end..]);
+}
+
+pub unsafe fn accumulate_sse2(acc: &mut [f32], row: &[f32], weight: f32) {
+    let w = _mm_set1_ps(weight);
+    let end = acc.len() & !3;
+    let mut i = 0;
+    while i < end {
+        let a = acc.as_mut_ptr().add(i);
+        let v = _mm_mul_ps(_mm_loadu_ps(row.as_ptr().add(i)), w);
+        _mm_storeu_ps(a, _mm_add_ps(_mm_loadu_ps(a), v));
+        i += 4;
+    }
+    scalar::accumulate(&mut acc[end..], &row[end..], weight);
+}
+
+#[target_feature(enable = "avx2")]
+pub unsafe fn accumulate_avx2(acc: &mut [f32], row: &[f32], weight: f32) {
+    let w = _mm256_set1_ps(weight);
+    let end = acc.len() & !7;
+    let mut i = 0;
+    while i < end {
+        let a = acc.as_mut_ptr().add(i);
+        let v = _mm256_mul_ps(_mm256_loadu_ps(row.as_ptr().add(i)), w);
+        _mm256_storeu_ps(a, _mm256_add_ps(_mm256_loadu_ps(a), v));
+        i += 8;
+    }
+    scalar::accumulate(&mut acc[end..], &row[end..], weight);
+}
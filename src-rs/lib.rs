@@ -1,20 +1,39 @@
 #![no_std]
 #[macro_use]
 extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
 
-use wasm_bindgen::{prelude::*, Clamped};
-use web_sys::ImageData;
+use alloc::vec::Vec;
+use libm::hypotf;
 
+mod codec;
+mod detector;
 mod image;
-use image::{Quad, RGBAImage};
+mod pdf;
+mod tracker;
+pub use codec::{
+    compress, decode_jpeg, decode_png, decompress, encode_jpeg, encode_jpeg_gray, encode_png,
+    encode_png_bits, encode_png_gray, DecodeError, DecodedJpeg, EncodeError, JpegOptions, PngColor,
+    PngOptions, Subsampling,
+};
+pub use detector::Detector;
+pub use image::{
+    aspect_ratio, snap_aspect, BinarizeOptions, BitImage, Border, ColorOptions, DeskewOptions,
+    DetectOptions, EnhanceOptions, ExtractOptions, Fill, GradientVotesResult, GrayImage, Image,
+    Interpolation, Line, Orientation, PaperSize, Point, Quad, RGBAImage, ScoredQuad, Threshold,
+    WarpOptions,
+};
+pub use pdf::{to_pdf, Pdf, PdfColor, PdfImage, PdfInfo};
+pub use tracker::DocumentTracker;
 
-#[cfg(not(target_arch = "wasm32"))]
-compile_error!("Only compilable to WASM");
+#[cfg(feature = "wasm")]
+mod wasm;
 
 fn sum_sides(quad: Quad) -> (f32, f32) {
     let Quad { a, b, c, d } = quad;
-    let side = (a.x - b.x).hypot(a.y - b.y) + (c.x - d.x).hypot(c.y - d.y);
-    let top = (b.x - c.x).hypot(b.y - c.y) + (d.x - a.x).hypot(d.y - a.y);
+    let side = hypotf(a.x - b.x, a.y - b.y) + hypotf(c.x - d.x, c.y - d.y);
+    let top = hypotf(b.x - c.x, b.y - c.y) + hypotf(d.x - a.x, d.y - a.y);
     (side, top)
 }
 
@@ -71,104 +90,182 @@ fn sort_quad(quad: Quad) -> Quad {
     }
 }
 
-impl From<ImageData> for RGBAImage {
-    fn from(data: ImageData) -> Self {
-        let width = data.width() as usize;
-        let height = data.height() as usize;
-        let data = data.data().0;
-        RGBAImage {
-            data,
-            width,
-            height,
-        }
+fn scale_quad(quad: Quad, by: f32) -> Quad {
+    let mut quad = quad;
+    quad.a.x *= by;
+    quad.a.y *= by;
+    quad.b.x *= by;
+    quad.b.y *= by;
+    quad.c.x *= by;
+    quad.c.y *= by;
+    quad.d.x *= by;
+    quad.d.y *= by;
+    quad
+}
+
+// whether every corner of one quad is within max_dist of some corner of the other
+fn same_quad(q1: Quad, q2: Quad, max_dist: f32) -> bool {
+    let corners = [q2.a, q2.b, q2.c, q2.d];
+    [q1.a, q1.b, q1.c, q1.d].iter().all(|p| {
+        corners
+            .iter()
+            .any(|q| hypotf(p.x - q.x, p.y - q.y) <= max_dist)
+    })
+}
+
+// how much a frame is downscaled before detection
+fn detection_scale(rgba: &RGBAImage, options: &DetectOptions) -> f32 {
+    let by = (rgba.width.min(rgba.height) as f32) / options.resolution;
+    if by < 2.0 {
+        1.0
+    } else {
+        by
     }
 }
 
-// use js_sys::Array;
-// #[wasm_bindgen]
-// pub fn find_edges(data: ImageData, threshold: f32) -> Array {
-//     console_error_panic_hook::set_once();
-//     let rgba: RGBAImage = data.into();
-//     let mut by = (rgba.width.min(rgba.height) as f32) / 360.0;
-//     if by < 2.0 {
-//         by = 1.0
-//     }
-//     let mut src = rgba.to_grayscale();
-//     if by != 1.0 {
-//         src = src.downscale(by);
-//     }
-//     src.gaussian().edges(threshold).into_iter().map(JsValue::from).collect()
-// }
-
-#[macro_export]
-macro_rules! perf {
-    ($b:expr) => {{
-        use js_sys::{global, Reflect};
-        use wasm_bindgen::{prelude::*, JsCast};
-        use web_sys::Performance;
-
-        #[wasm_bindgen]
-        extern "C" {
-            #[wasm_bindgen(js_namespace = console)]
-            fn log(a: &str, b: &str, c: &str, d: f64);
+fn prepare(rgba: &RGBAImage, options: &DetectOptions) -> (Image, f32) {
+    let by = detection_scale(rgba, options);
+    let src = if by != 1.0 {
+        rgba.to_grayscale_downscaled(by)
+    } else {
+        rgba.to_grayscale()
+    };
+    (src.gaussian(), by)
+}
+
+// maps a quad found on the detection image back onto the full frame
+fn to_frame(rgba: &RGBAImage, quad: Quad, by: f32, options: &DetectOptions) -> Quad {
+    rgba.refine(scale_quad(quad, by), options.refine_band * by)
+}
+
+pub fn find_document(rgba: &RGBAImage, options: &DetectOptions) -> Option<ScoredQuad> {
+    Detector::new(*options).find(rgba)
+}
+
+pub fn find_documents(rgba: &RGBAImage, options: &DetectOptions, max: usize) -> Vec<ScoredQuad> {
+    let options = &options.clamped();
+    let (src, by) = prepare(rgba, options);
+    let max_dist = hypotf(src.width as f32, src.height as f32) * options.duplicate_distance;
+    let mut found: Vec<ScoredQuad> = Vec::new();
+    for doc in src.documents(options) {
+        if found.len() >= max {
+            break;
+        }
+        let quad = sort_quad(doc.quad);
+        if !found
+            .iter()
+            .any(|other| same_quad(quad, other.quad, max_dist))
+        {
+            found.push(ScoredQuad { quad, ..doc });
         }
-        let performance = Reflect::get(&global(), &JsValue::from_str("performance"))
-            .unwrap()
-            .unchecked_into::<Performance>();
-        let ts = performance.now();
-        let ret = $b;
-        log("time", stringify!($b), "=", performance.now() - ts);
-        ret
-    }};
+    }
+    for doc in found.iter_mut() {
+        doc.quad = to_frame(rgba, doc.quad, by, options);
+    }
+    found
 }
 
-#[wasm_bindgen]
-pub fn find_document(data: ImageData) -> Option<Quad> {
-    #[cfg(debug_assertions)]
-    console_error_panic_hook::set_once();
-    let rgba: RGBAImage = data.into();
-    let mut by = (rgba.width.min(rgba.height) as f32) / 360.0;
-    if by < 2.0 {
-        by = 1.0
+pub fn find_all_documents(
+    rgba: &RGBAImage,
+    options: &DetectOptions,
+    max: usize,
+) -> Vec<ScoredQuad> {
+    let options = &options.clamped();
+    let (src, by) = prepare(rgba, options);
+    let mut found = src.separate_documents(options, max);
+    for doc in found.iter_mut() {
+        doc.quad = to_frame(rgba, sort_quad(doc.quad), by, options);
+    }
+    found
+}
+
+fn target_height(
+    rgba: &RGBAImage,
+    region: Quad,
+    target_width: usize,
+    target_height: Option<usize>,
+    options: &ExtractOptions,
+) -> usize {
+    if let Some(height) = target_height {
+        return height;
     }
-    let mut src = rgba.to_grayscale();
-    if by != 1.0 {
-        src = src.downscale(by);
+    let mut ratio = aspect_ratio(region, rgba.width, rgba.height, options.focal_length);
+    if options.snap_paper {
+        ratio = snap_aspect(ratio, options.snap_tolerance).0;
     }
-    src.gaussian().document().map(|doc| {
-        let mut doc = sort_quad(doc.quad);
-        doc.a.x *= by;
-        doc.a.y *= by;
-        doc.b.x *= by;
-        doc.b.y *= by;
-        doc.c.x *= by;
-        doc.c.y *= by;
-        doc.d.x *= by;
-        doc.d.y *= by;
-        doc
-    })
+    if !ratio.is_finite() || ratio <= 0.0 {
+        let (side, top) = sum_sides(region);
+        ratio = top / side;
+    }
+    (target_width as f32 / ratio) as usize
 }
 
-#[wasm_bindgen]
 pub fn extract_document(
-    data: ImageData,
+    rgba: &RGBAImage,
     region: Quad,
     target_width: usize,
     target_height: Option<usize>,
-) -> ImageData {
-    #[cfg(debug_assertions)]
-    console_error_panic_hook::set_once();
-    let rgba: RGBAImage = data.into();
-    let target_height = if let Some(height) = target_height {
-        height
+    options: &ExtractOptions,
+) -> RGBAImage {
+    let target_height = self::target_height(rgba, region, target_width, target_height, options);
+    let mut page = rgba.perspective(region, target_width, target_height, &options.warp);
+    if options.deskew {
+        page = page.deskew(&options.skew, &options.warp);
+    }
+    if options.auto_rotate {
+        let orientation = page.to_grayscale().orientation();
+        if orientation.turns != 0 && orientation.confidence >= options.rotate_confidence {
+            page = page.rotate(orientation.turns, false);
+        }
+    }
+    if options.correct_color {
+        page.correct_color(&options.color)
     } else {
-        let (side, top) = sum_sides(region);
-        (side / top * (target_width as f32)) as usize
-    };
-    ImageData::new_with_u8_clamped_array_and_sh(
-        Clamped(&rgba.perspective(region, target_width, target_height).data),
-        target_width as u32,
-        target_height as u32,
+        page
+    }
+}
+
+// 8-bit luminance straight from the frame; deskew, auto-rotate and color correction need
+// color and are skipped
+pub fn extract_document_gray(
+    rgba: &RGBAImage,
+    region: Quad,
+    target_width: usize,
+    target_height: Option<usize>,
+    options: &ExtractOptions,
+) -> GrayImage {
+    let target_height = self::target_height(rgba, region, target_width, target_height, options);
+    rgba.perspective_gray8(region, target_width, target_height, &options.warp)
+}
+
+// 1-bit page where luminance below the threshold (0-1) is ink; the same passes as
+// extract_document_gray are skipped
+pub fn extract_document_bits(
+    rgba: &RGBAImage,
+    region: Quad,
+    target_width: usize,
+    target_height: Option<usize>,
+    threshold: f32,
+    options: &ExtractOptions,
+) -> BitImage {
+    let target_height = self::target_height(rgba, region, target_width, target_height, options);
+    rgba.perspective_bits(
+        region,
+        target_width,
+        target_height,
+        &options.warp,
+        threshold,
     )
-    .unwrap()
+}
+
+pub fn extract_documents(
+    rgba: &RGBAImage,
+    regions: &[Quad],
+    target_width: usize,
+    options: &ExtractOptions,
+) -> Vec<RGBAImage> {
+    regions
+        .iter()
+        .map(|&region| extract_document(rgba, region, target_width, None, options))
+        .collect()
 }
@@ -0,0 +1,358 @@
+use super::{compress, BitImage, EncodeError, GrayImage, JpegOptions, RGBAImage};
+use alloc::{string::String, vec::Vec};
+use core::fmt::Write;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PdfColor {
+    Gray,
+    Rgb,
+}
+
+// an image XObject, already encoded for the given filter
+pub struct PdfImage {
+    pub width: usize,
+    pub height: usize,
+    pub color: PdfColor,
+    pub bits_per_component: u8,
+    // e.g. DCTDecode or FlateDecode; None for raw samples
+    pub filter: Option<&'static str>,
+    pub data: Vec<u8>,
+}
+
+impl PdfImage {
+    // lossless 8-bit RGB, composited onto white
+    pub fn from_rgba(image: &RGBAImage) -> PdfImage {
+        let mut data = Vec::with_capacity(image.width * image.height * 3);
+        for px in image.data.chunks_exact(4) {
+            let alpha = px[3] as u32;
+            let white = 255 * (255 - alpha);
+            for &c in &px[..3] {
+                data.push(((c as u32 * alpha + white + 127) / 255) as u8);
+            }
+        }
+        PdfImage {
+            width: image.width,
+            height: image.height,
+            color: PdfColor::Rgb,
+            bits_per_component: 8,
+            filter: Some("FlateDecode"),
+            data: compress(&data, 6),
+        }
+    }
+
+    pub fn from_gray(image: &GrayImage) -> PdfImage {
+        PdfImage {
+            width: image.width,
+            height: image.height,
+            color: PdfColor::Gray,
+            bits_per_component: 8,
+            filter: Some("FlateDecode"),
+            data: compress(&image.data, 6),
+        }
+    }
+
+    // 1-bit samples share BitImage's layout, with 0 as black
+    pub fn from_bits(image: &BitImage) -> PdfImage {
+        PdfImage {
+            width: image.width,
+            height: image.height,
+            color: PdfColor::Gray,
+            bits_per_component: 1,
+            filter: Some("FlateDecode"),
+            data: compress(&image.data, 9),
+        }
+    }
+
+    pub fn from_jpeg(image: &RGBAImage, options: &JpegOptions) -> Result<PdfImage, EncodeError> {
+        Ok(PdfImage {
+            width: image.width,
+            height: image.height,
+            color: if options.grayscale {
+                PdfColor::Gray
+            } else {
+                PdfColor::Rgb
+            },
+            bits_per_component: 8,
+            filter: Some("DCTDecode"),
+            data: image.to_jpeg(options)?,
+        })
+    }
+}
+
+// document information dictionary; dates are PDF date strings like D:20240131120000Z
+#[derive(Clone, Default)]
+pub struct PdfInfo {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub subject: Option<String>,
+    pub keywords: Option<String>,
+    pub creator: Option<String>,
+    pub producer: Option<String>,
+    pub creation_date: Option<String>,
+}
+
+pub struct Pdf {
+    buf: Vec<u8>,
+    // byte offset of each object, indexed by object number - 1
+    offsets: Vec<usize>,
+    pages_id: usize,
+    kids: Vec<usize>,
+}
+
+impl Pdf {
+    pub fn new() -> Pdf {
+        let mut pdf = Pdf {
+            buf: Vec::new(),
+            offsets: Vec::new(),
+            pages_id: 0,
+            kids: Vec::new(),
+        };
+        // v1.4 for compatibility, plus a binary comment as suggested by the spec
+        pdf.put("%PDF-1.4\n%");
+        pdf.buf.extend_from_slice(&[0x90, 0x85, 0xfa, 0xe3, b'\n']);
+        pdf.pages_id = pdf.reserve();
+        pdf
+    }
+
+    fn put(&mut self, s: &str) {
+        self.buf.extend_from_slice(s.as_bytes());
+    }
+
+    fn reserve(&mut self) -> usize {
+        self.offsets.push(0);
+        self.offsets.len()
+    }
+
+    fn begin(&mut self, id: usize) {
+        self.offsets[id - 1] = self.buf.len();
+        self.put(&format!("{} 0 obj\n", id));
+    }
+
+    fn end(&mut self) {
+        self.put("\nendobj\n");
+    }
+
+    fn stream(&mut self, dict: &str, content: &[u8]) {
+        self.put(&format!("<<{} /Length {}>>\nstream\n", dict, content.len()));
+        self.buf.extend_from_slice(content);
+        self.put("\nendstream");
+    }
+
+    // adds a page showing the image at the given resolution
+    pub fn add_image(&mut self, image: &PdfImage, dpi: f32) -> Result<(), EncodeError> {
+        if !(dpi > 0.0 && dpi.is_finite()) {
+            return Err(EncodeError::Invalid("dpi must be positive"));
+        }
+        if image.width == 0 || image.height == 0 {
+            return Err(EncodeError::Size("PDF pages must not be empty"));
+        }
+        let width = image.width as f32 * 72.0 / dpi;
+        let height = image.height as f32 * 72.0 / dpi;
+
+        let image_id = self.reserve();
+        self.begin(image_id);
+        let mut dict = format!(
+            "/Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace /{} /BitsPerComponent {}",
+            image.width,
+            image.height,
+            match image.color {
+                PdfColor::Gray => "DeviceGray",
+                PdfColor::Rgb => "DeviceRGB",
+            },
+            image.bits_per_component
+        );
+        if let Some(filter) = image.filter {
+            write!(dict, " /Filter /{}", filter).unwrap();
+        }
+        self.stream(&dict, &image.data);
+        self.end();
+
+        let contents_id = self.reserve();
+        self.begin(contents_id);
+        let contents = format!("q {:.3} 0 0 {:.3} 0 0 cm /I Do Q", width, height);
+        self.stream("", contents.as_bytes());
+        self.end();
+
+        let page_id = self.reserve();
+        self.begin(page_id);
+        self.put(&format!(
+            "<</Type /Page /Parent {} 0 R /Resources <</XObject <</I {} 0 R>>>> /Contents {} 0 R /MediaBox [0 0 {:.3} {:.3}]>>",
+            self.pages_id, image_id, contents_id, width, height
+        ));
+        self.end();
+        self.kids.push(page_id);
+        Ok(())
+    }
+
+    pub fn add_page(&mut self, page: &RGBAImage, dpi: f32) -> Result<(), EncodeError> {
+        self.add_image(&PdfImage::from_rgba(page), dpi)
+    }
+
+    pub fn finish(mut self, info: &PdfInfo) -> Vec<u8> {
+        let pages_id = self.pages_id;
+        self.begin(pages_id);
+        let mut kids = String::new();
+        for (i, kid) in self.kids.iter().enumerate() {
+            if i != 0 {
+                kids.push(' ');
+            }
+            write!(kids, "{} 0 R", kid).unwrap();
+        }
+        self.put(&format!(
+            "<</Type /Pages /Kids [{}] /Count {}>>",
+            kids,
+            self.kids.len()
+        ));
+        self.end();
+
+        let catalog_id = self.reserve();
+        self.begin(catalog_id);
+        self.put(&format!("<</Type /Catalog /Pages {} 0 R>>", pages_id));
+        self.end();
+
+        let info_id = self.reserve();
+        self.begin(info_id);
+        let mut dict = String::new();
+        for (key, value) in [
+            ("Title", &info.title),
+            ("Author", &info.author),
+            ("Subject", &info.subject),
+            ("Keywords", &info.keywords),
+            ("Creator", &info.creator),
+            ("Producer", &info.producer),
+            ("CreationDate", &info.creation_date),
+        ] {
+            if let Some(value) = value {
+                write!(dict, "/{} ", key).unwrap();
+                text(&mut dict, value);
+            }
+        }
+        self.put(&format!("<<{}>>", dict));
+        self.end();
+
+        let xref = self.buf.len();
+        let mut table = format!("xref\n0 {}\n0000000000 65535 f \n", self.offsets.len() + 1);
+        for offset in &self.offsets {
+            writeln!(table, "{:010} 00000 n ", offset).unwrap();
+        }
+        write!(
+            table,
+            "trailer\n<</Size {} /Root {} 0 R /Info {} 0 R>>\nstartxref\n{}\n%%EOF\n",
+            self.offsets.len() + 1,
+            catalog_id,
+            info_id,
+            xref
+        )
+        .unwrap();
+        self.put(&table);
+        self.buf
+    }
+}
+
+impl Default for Pdf {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// PDF text string: escaped literal if ASCII, UTF-16BE hex otherwise
+fn text(out: &mut String, value: &str) {
+    if value.is_ascii() {
+        out.push('(');
+        for c in value.chars() {
+            match c {
+                '(' | ')' | '\\' => {
+                    out.push('\\');
+                    out.push(c);
+                }
+                '\n' => out.push_str("\\n"),
+                '\r' => out.push_str("\\r"),
+                _ => out.push(c),
+            }
+        }
+        out.push(')');
+    } else {
+        out.push_str("<FEFF");
+        for unit in value.encode_utf16() {
+            write!(out, "{:04X}", unit).unwrap();
+        }
+        out.push('>');
+    }
+}
+
+// pages are stored as JPEGs if options are given, losslessly otherwise
+pub fn to_pdf(
+    pages: &[RGBAImage],
+    dpi: f32,
+    jpeg: Option<&JpegOptions>,
+    info: &PdfInfo,
+) -> Result<Vec<u8>, EncodeError> {
+    let mut pdf = Pdf::new();
+    for page in pages {
+        match jpeg {
+            Some(options) => pdf.add_image(&PdfImage::from_jpeg(page, options)?, dpi)?,
+            None => pdf.add_page(page, dpi)?,
+        }
+    }
+    Ok(pdf.finish(info))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{to_pdf, EncodeError, JpegOptions, PdfInfo, RGBAImage};
+
+    fn page(width: usize, height: usize) -> RGBAImage {
+        RGBAImage {
+            data: (0..width * height * 4).map(|i| (i * 7) as u8).collect(),
+            width,
+            height,
+        }
+    }
+
+    fn number(buf: &[u8], at: usize) -> usize {
+        buf[at..]
+            .iter()
+            .take_while(|c| c.is_ascii_digit())
+            .fold(0, |n, &c| n * 10 + (c - b'0') as usize)
+    }
+
+    #[test]
+    fn xref_points_at_objects() {
+        let info = PdfInfo {
+            title: Some("scan (1)".into()),
+            ..Default::default()
+        };
+        for jpeg in [None, Some(&JpegOptions::new())] {
+            let pdf = to_pdf(&[page(30, 20), page(17, 40)], 150.0, jpeg, &info).unwrap();
+            let start = pdf.windows(10).rposition(|w| w == b"startxref\n").unwrap();
+            let xref = number(&pdf, start + 10);
+            assert!(pdf[xref..].starts_with(b"xref\n0 "));
+            let count = number(&pdf, xref + 7);
+            // two pages of three objects, plus the page tree, catalog and info
+            assert_eq!(count, 10);
+            let entries = xref + 7 + format!("{}", count).len() + 1;
+            for id in 1..count {
+                let entry = entries + id * 20;
+                assert_eq!(&pdf[entry + 10..entry + 20], b" 00000 n \n");
+                let offset = number(&pdf, entry);
+                let header = format!("{} 0 obj\n", id);
+                assert!(
+                    pdf[offset..].starts_with(header.as_bytes()),
+                    "object {}",
+                    id
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_bad_pages() {
+        let info = PdfInfo::default();
+        for dpi in [0.0, -72.0, f32::NAN, f32::INFINITY] {
+            let result = to_pdf(&[page(4, 4)], dpi, None, &info);
+            assert!(matches!(result, Err(EncodeError::Invalid(_))));
+        }
+        let result = to_pdf(&[page(0, 4)], 72.0, None, &info);
+        assert!(matches!(result, Err(EncodeError::Size(_))));
+    }
+}
@@ -0,0 +1,170 @@
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use super::{
+    detection_scale, image::Workspace, scale_quad, sort_quad, DetectOptions, Point, Quad,
+    RGBAImage, ScoredQuad,
+};
+use libm::hypotf;
+
+const SMOOTHING: f32 = 0.5;
+const TRACK_DISTANCE: f32 = 0.05;
+const TRACK_ANGLE: u8 = 12;
+const STILL_DISTANCE: f32 = 0.005;
+const HOLD_FRAMES: usize = 10;
+
+fn corners(quad: Quad) -> [Point; 4] {
+    [quad.a, quad.b, quad.c, quad.d]
+}
+
+fn from_corners(pts: [Point; 4]) -> Quad {
+    Quad {
+        a: pts[0],
+        b: pts[1],
+        c: pts[2],
+        d: pts[3],
+    }
+}
+
+// rotate the corners of quad so they line up with those of prev
+fn align(quad: Quad, prev: Quad) -> Quad {
+    let pts = corners(quad);
+    let prev = corners(prev);
+    let mut best = pts;
+    let mut best_dist = f32::INFINITY;
+    for rot in 0..4 {
+        let mut dist = 0.0;
+        for (i, p) in prev.iter().enumerate() {
+            let q = pts[(i + rot) & 3];
+            dist += hypotf(p.x - q.x, p.y - q.y);
+        }
+        if dist < best_dist {
+            best_dist = dist;
+            best = [
+                pts[rot],
+                pts[(rot + 1) & 3],
+                pts[(rot + 2) & 3],
+                pts[(rot + 3) & 3],
+            ];
+        }
+    }
+    from_corners(best)
+}
+
+fn max_movement(a: Quad, b: Quad) -> f32 {
+    corners(a)
+        .iter()
+        .zip(corners(b).iter())
+        .map(|(p, q)| hypotf(p.x - q.x, p.y - q.y))
+        .fold(0.0, f32::max)
+}
+
+fn lerp(from: Quad, to: Quad, t: f32) -> Quad {
+    let from = corners(from);
+    let to = corners(to);
+    let mut pts = from;
+    for i in 0..4 {
+        pts[i].x += (to[i].x - from[i].x) * t;
+        pts[i].y += (to[i].y - from[i].y) * t;
+    }
+    from_corners(pts)
+}
+
+// follows one document across live camera frames
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub struct DocumentTracker {
+    options: DetectOptions,
+    workspace: Workspace,
+    quad: Option<Quad>,
+    still_frames: usize,
+    // weight of the newest detection in the smoothed corners, 0-1
+    pub smoothing: f32,
+    // how far, relative to the frame diagonal, an edge may move between frames
+    pub track_distance: f32,
+    // how many angle bins an edge may rotate between frames
+    pub track_angle: u8,
+    // corner movement, relative to the frame diagonal, that still counts as holding still
+    pub still_distance: f32,
+    // number of still frames before the document is considered steady
+    pub hold_frames: usize,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl DocumentTracker {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new(options: DetectOptions) -> DocumentTracker {
+        DocumentTracker {
+            options: options.clamped(),
+            workspace: Workspace::default(),
+            quad: None,
+            still_frames: 0,
+            smoothing: SMOOTHING,
+            track_distance: TRACK_DISTANCE,
+            track_angle: TRACK_ANGLE,
+            still_distance: STILL_DISTANCE,
+            hold_frames: HOLD_FRAMES,
+        }
+    }
+    pub fn quad(&self) -> Option<Quad> {
+        self.quad
+    }
+    pub fn still_frames(&self) -> usize {
+        self.still_frames
+    }
+    pub fn is_steady(&self) -> bool {
+        self.quad.is_some() && self.still_frames >= self.hold_frames
+    }
+    pub fn reset(&mut self) {
+        self.quad = None;
+        self.still_frames = 0;
+    }
+}
+
+impl DocumentTracker {
+    pub fn update(&mut self, rgba: &RGBAImage) -> Option<ScoredQuad> {
+        let options = &self.options;
+        let by = detection_scale(rgba, options);
+        self.workspace.prepare(rgba, by);
+        self.workspace.vote(options);
+        let (width, height) = self.workspace.size();
+        let diag = hypotf(width as f32, height as f32);
+        // edges near the last quad first; if they make none, every edge of the same votes
+        let near = self.quad.and_then(|prev| {
+            self.workspace
+                .quads_near(
+                    options,
+                    scale_quad(prev, 1.0 / by),
+                    diag * self.track_distance,
+                    self.track_angle,
+                )
+                .first()
+                .copied()
+        });
+        let doc = match near.or_else(|| self.workspace.quads(options).first().copied()) {
+            Some(doc) => doc,
+            None => {
+                self.reset();
+                return None;
+            }
+        };
+        let found = self.workspace.refine(
+            rgba,
+            scale_quad(sort_quad(doc.quad), by),
+            options.refine_band * by,
+        );
+        let quad = match self.quad {
+            Some(prev) => {
+                let found = align(found, prev);
+                if max_movement(found, prev) <= diag * by * self.still_distance {
+                    self.still_frames += 1;
+                } else {
+                    self.still_frames = 0;
+                }
+                lerp(prev, found, self.smoothing)
+            }
+            None => found,
+        };
+        self.quad = Some(quad);
+        Some(ScoredQuad { quad, ..doc })
+    }
+}
@@ -0,0 +1,340 @@
+use alloc::{string::String, vec::Vec};
+use js_sys::{Array, Date, Reflect, Uint8ClampedArray};
+use wasm_bindgen::{prelude::*, Clamped, JsCast};
+use web_sys::ImageData;
+
+use super::{
+    BinarizeOptions, ColorOptions, DeskewOptions, DetectOptions, Detector, DocumentTracker,
+    EnhanceOptions, ExtractOptions, JpegOptions, Orientation, PdfInfo, PngOptions, Quad, RGBAImage,
+    ScoredQuad, WarpOptions,
+};
+
+#[cfg(all(feature = "parallel", target_arch = "wasm32"))]
+mod threads;
+
+impl From<ImageData> for RGBAImage {
+    fn from(data: ImageData) -> Self {
+        let width = data.width() as usize;
+        let height = data.height() as usize;
+        let data = data.data().0;
+        RGBAImage {
+            data,
+            width,
+            height,
+        }
+    }
+}
+
+fn to_image_data(image: &RGBAImage) -> ImageData {
+    ImageData::new_with_u8_clamped_array_and_sh(
+        Clamped(&image.data),
+        image.width as u32,
+        image.height as u32,
+    )
+    .unwrap()
+}
+
+// #[wasm_bindgen]
+// pub fn find_edges(data: ImageData, threshold: f32) -> Array {
+//     console_error_panic_hook::set_once();
+//     let rgba: RGBAImage = data.into();
+//     let mut by = (rgba.width.min(rgba.height) as f32) / 360.0;
+//     if by < 2.0 {
+//         by = 1.0
+//     }
+//     let mut src = rgba.to_grayscale();
+//     if by != 1.0 {
+//         src = src.downscale(by);
+//     }
+//     src.gaussian().edges(threshold).into_iter().map(JsValue::from).collect()
+// }
+
+#[macro_export]
+macro_rules! perf {
+    ($b:expr) => {{
+        use js_sys::{global, Reflect};
+        use wasm_bindgen::{prelude::*, JsCast};
+        use web_sys::Performance;
+
+        #[wasm_bindgen]
+        extern "C" {
+            #[wasm_bindgen(js_namespace = console)]
+            fn log(a: &str, b: &str, c: &str, d: f64);
+        }
+        let performance = Reflect::get(&global(), &JsValue::from_str("performance"))
+            .unwrap()
+            .unchecked_into::<Performance>();
+        let ts = performance.now();
+        let ret = $b;
+        log("time", stringify!($b), "=", performance.now() - ts);
+        ret
+    }};
+}
+
+#[wasm_bindgen]
+pub fn find_document(data: ImageData, options: Option<DetectOptions>) -> Option<ScoredQuad> {
+    #[cfg(debug_assertions)]
+    console_error_panic_hook::set_once();
+    super::find_document(&data.into(), &options.unwrap_or_default())
+}
+
+#[wasm_bindgen]
+pub fn find_documents(data: ImageData, max: usize, options: Option<DetectOptions>) -> Array {
+    #[cfg(debug_assertions)]
+    console_error_panic_hook::set_once();
+    super::find_documents(&data.into(), &options.unwrap_or_default(), max)
+        .into_iter()
+        .map(JsValue::from)
+        .collect()
+}
+
+#[wasm_bindgen]
+pub fn find_all_documents(data: ImageData, max: usize, options: Option<DetectOptions>) -> Array {
+    #[cfg(debug_assertions)]
+    console_error_panic_hook::set_once();
+    super::find_all_documents(&data.into(), &options.unwrap_or_default(), max)
+        .into_iter()
+        .map(JsValue::from)
+        .collect()
+}
+
+#[wasm_bindgen]
+impl Detector {
+    pub fn detect(&mut self, frame: &ImageData) -> Option<ScoredQuad> {
+        #[cfg(debug_assertions)]
+        console_error_panic_hook::set_once();
+        // ImageData::data would copy the pixels into a new Vec, so copy into the kept one instead
+        let pixels: Uint8ClampedArray = Reflect::get(frame, &JsValue::from_str("data"))
+            .unwrap_throw()
+            .unchecked_into();
+        let mut rgba = core::mem::take(&mut self.frame);
+        rgba.width = frame.width() as usize;
+        rgba.height = frame.height() as usize;
+        rgba.data.resize(pixels.length() as usize, 0);
+        pixels.copy_to(&mut rgba.data);
+        let found = self.find(&rgba);
+        self.frame = rgba;
+        found
+    }
+}
+
+#[wasm_bindgen]
+impl DocumentTracker {
+    pub fn track(&mut self, data: ImageData) -> Option<ScoredQuad> {
+        #[cfg(debug_assertions)]
+        console_error_panic_hook::set_once();
+        self.update(&data.into())
+    }
+}
+
+#[wasm_bindgen]
+pub fn extract_document(
+    data: ImageData,
+    region: Quad,
+    target_width: usize,
+    target_height: Option<usize>,
+    extract: Option<ExtractOptions>,
+) -> ImageData {
+    #[cfg(debug_assertions)]
+    console_error_panic_hook::set_once();
+    let out = super::extract_document(
+        &data.into(),
+        region,
+        target_width,
+        target_height,
+        &extract.unwrap_or_default(),
+    );
+    to_image_data(&out)
+}
+
+// 8-bit luminance rows of target_width bytes
+#[wasm_bindgen]
+pub fn extract_document_gray(
+    data: ImageData,
+    region: Quad,
+    target_width: usize,
+    target_height: Option<usize>,
+    extract: Option<ExtractOptions>,
+) -> Vec<u8> {
+    #[cfg(debug_assertions)]
+    console_error_panic_hook::set_once();
+    super::extract_document_gray(
+        &data.into(),
+        region,
+        target_width,
+        target_height,
+        &extract.unwrap_or_default(),
+    )
+    .data
+}
+
+// packed 1-bit rows, most significant bit first and padded to whole bytes; set bits are paper
+#[wasm_bindgen]
+pub fn extract_document_bits(
+    data: ImageData,
+    region: Quad,
+    target_width: usize,
+    target_height: Option<usize>,
+    threshold: f32,
+    extract: Option<ExtractOptions>,
+) -> Vec<u8> {
+    #[cfg(debug_assertions)]
+    console_error_panic_hook::set_once();
+    super::extract_document_bits(
+        &data.into(),
+        region,
+        target_width,
+        target_height,
+        threshold,
+        &extract.unwrap_or_default(),
+    )
+    .data
+}
+
+// detects every document in the frame and extracts them all
+#[wasm_bindgen]
+pub fn extract_all_documents(
+    data: ImageData,
+    max: usize,
+    target_width: usize,
+    options: Option<DetectOptions>,
+    extract: Option<ExtractOptions>,
+) -> Array {
+    #[cfg(debug_assertions)]
+    console_error_panic_hook::set_once();
+    let rgba: RGBAImage = data.into();
+    let regions: Vec<Quad> = super::find_all_documents(&rgba, &options.unwrap_or_default(), max)
+        .into_iter()
+        .map(|doc| doc.quad)
+        .collect();
+    super::extract_documents(&rgba, &regions, target_width, &extract.unwrap_or_default())
+        .iter()
+        .map(|out| JsValue::from(to_image_data(out)))
+        .collect()
+}
+
+// quarter turns clockwise that make the text on an extracted page upright
+#[wasm_bindgen]
+pub fn orientation(data: ImageData) -> Orientation {
+    #[cfg(debug_assertions)]
+    console_error_panic_hook::set_once();
+    let rgba: RGBAImage = data.into();
+    rgba.to_grayscale().orientation()
+}
+
+// straightens text that is slightly rotated within an extracted page
+#[wasm_bindgen]
+pub fn deskew(
+    data: ImageData,
+    options: Option<DeskewOptions>,
+    warp: Option<WarpOptions>,
+) -> ImageData {
+    #[cfg(debug_assertions)]
+    console_error_panic_hook::set_once();
+    let rgba: RGBAImage = data.into();
+    to_image_data(&rgba.deskew(&options.unwrap_or_default(), &warp.unwrap_or_default()))
+}
+
+// white balance and contrast stretch for an extracted page
+#[wasm_bindgen]
+pub fn correct_color(data: ImageData, options: Option<ColorOptions>) -> ImageData {
+    #[cfg(debug_assertions)]
+    console_error_panic_hook::set_once();
+    let rgba: RGBAImage = data.into();
+    to_image_data(&rgba.correct_color(&options.unwrap_or_default()))
+}
+
+// evens out shadows and lighting on an extracted page
+#[wasm_bindgen]
+pub fn enhance(data: ImageData, options: Option<EnhanceOptions>) -> ImageData {
+    #[cfg(debug_assertions)]
+    console_error_panic_hook::set_once();
+    let rgba: RGBAImage = data.into();
+    to_image_data(&rgba.enhance(&options.unwrap_or_default()))
+}
+
+// black and white version of an extracted page, as opaque RGBA
+#[wasm_bindgen]
+pub fn binarize(data: ImageData, options: Option<BinarizeOptions>) -> ImageData {
+    #[cfg(debug_assertions)]
+    console_error_panic_hook::set_once();
+    let rgba: RGBAImage = data.into();
+    let bw = rgba.to_grayscale().binarize(&options.unwrap_or_default());
+    to_image_data(&RGBAImage {
+        data: bw.data.iter().flat_map(|&v| [v, v, v, 255]).collect(),
+        width: bw.width,
+        height: bw.height,
+    })
+}
+
+#[wasm_bindgen]
+pub fn encode_jpeg(data: ImageData, options: Option<JpegOptions>) -> Result<Vec<u8>, JsValue> {
+    #[cfg(debug_assertions)]
+    console_error_panic_hook::set_once();
+    super::encode_jpeg(&data.into(), &options.unwrap_or_default())
+        .map_err(|err| JsValue::from_str(&format!("{}", err)))
+}
+
+#[wasm_bindgen(getter_with_clone)]
+pub struct DecodedImage {
+    // upright pixels
+    pub data: ImageData,
+    pub orientation: u8,
+    // camera focal length in pixels, or 0 if unknown
+    pub focal_length: f32,
+}
+
+#[wasm_bindgen]
+pub fn decode_jpeg(bytes: &[u8]) -> Result<DecodedImage, JsValue> {
+    #[cfg(debug_assertions)]
+    console_error_panic_hook::set_once();
+    let decoded =
+        super::decode_jpeg(bytes).map_err(|err| JsValue::from_str(&format!("{}", err)))?;
+    Ok(DecodedImage {
+        data: to_image_data(&decoded.image),
+        orientation: decoded.orientation,
+        focal_length: decoded.focal_length,
+    })
+}
+
+#[wasm_bindgen]
+pub fn encode_png(data: ImageData, options: Option<PngOptions>) -> Result<Vec<u8>, JsValue> {
+    #[cfg(debug_assertions)]
+    console_error_panic_hook::set_once();
+    super::encode_png(&data.into(), &options.unwrap_or_default())
+        .map_err(|err| JsValue::from_str(&format!("{}", err)))
+}
+
+#[wasm_bindgen]
+pub fn to_pdf(
+    pages: Array,
+    dpi: f32,
+    jpeg: Option<JpegOptions>,
+    title: Option<String>,
+    author: Option<String>,
+) -> Result<Vec<u8>, JsValue> {
+    #[cfg(debug_assertions)]
+    console_error_panic_hook::set_once();
+    let pages: Vec<RGBAImage> = pages
+        .iter()
+        .map(|page| page.unchecked_into::<ImageData>().into())
+        .collect();
+    let now = Date::new_0();
+    let info = PdfInfo {
+        title,
+        author,
+        producer: Some("scanner".into()),
+        creation_date: Some(format!(
+            "D:{:04}{:02}{:02}{:02}{:02}{:02}Z",
+            now.get_utc_full_year(),
+            now.get_utc_month() + 1,
+            now.get_utc_date(),
+            now.get_utc_hours(),
+            now.get_utc_minutes(),
+            now.get_utc_seconds()
+        )),
+        ..Default::default()
+    };
+    super::to_pdf(&pages, dpi, jpeg.as_ref(), &info)
+        .map_err(|err| JsValue::from_str(&format!("{}", err)))
+}
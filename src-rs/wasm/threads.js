@@ -0,0 +1,39 @@
+// workers for the rayon pool in threads.rs; this file is both the module that starts them
+// and the script each of them runs
+
+const waitFor = (target, type) => new Promise(resolve => {
+  const onMessage = ({ data }) => {
+    if (data && data.type == type) {
+      target.removeEventListener('message', onMessage);
+      resolve(data);
+    }
+  };
+  target.addEventListener('message', onMessage);
+});
+
+waitFor(self, 'scanner-thread-init').then(async ({ module, memory, receiver }) => {
+  // wasm-bindgen places snippets at pkg/snippets/<crate>/src-rs/wasm/
+  const pkg = await import('../../../../scanner.js');
+  await pkg.default({ module_or_path: module, memory });
+  postMessage({ type: 'scanner-thread-ready' });
+  pkg.start_thread(receiver);
+});
+
+let workers;
+
+export async function startWorkers(module, memory, builder) {
+  const init = {
+    type: 'scanner-thread-init',
+    module,
+    memory,
+    receiver: builder.receiver()
+  };
+  // kept referenced so they aren't collected
+  workers = await Promise.all(Array.from({ length: builder.threads() }, async () => {
+    const worker = new Worker(new URL('./threads.js', import.meta.url), { type: 'module' });
+    worker.postMessage(init);
+    await waitFor(worker, 'scanner-thread-ready');
+    return worker;
+  }));
+  builder.build();
+}
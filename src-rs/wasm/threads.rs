@@ -0,0 +1,74 @@
+// rayon's pool on wasm: threads.js starts web workers that instantiate this same module on the
+// same shared memory, and each one becomes a rayon thread that runs for the life of the page
+
+use js_sys::Promise;
+use rayon::{ThreadBuilder, ThreadPoolBuilder};
+use std::sync::{
+    mpsc::{channel, Receiver, Sender},
+    Mutex,
+};
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen(module = "/src-rs/wasm/threads.js")]
+extern "C" {
+    #[wasm_bindgen(js_name = startWorkers)]
+    fn start_workers(module: JsValue, memory: JsValue, builder: PoolBuilder) -> Promise;
+}
+
+type Threads = Mutex<Receiver<ThreadBuilder>>;
+
+#[wasm_bindgen]
+pub struct PoolBuilder {
+    threads: usize,
+    sender: Sender<ThreadBuilder>,
+    receiver: Threads,
+}
+
+#[wasm_bindgen]
+impl PoolBuilder {
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    // handed to every worker, which waits on it for the thread it should run
+    pub fn receiver(&self) -> *const Threads {
+        &self.receiver
+    }
+
+    // called once every worker is waiting, so each thread rayon hands out starts right away
+    pub fn build(&mut self) {
+        let sender = &self.sender;
+        ThreadPoolBuilder::new()
+            .num_threads(self.threads)
+            .spawn_handler(|thread| {
+                sender.send(thread).unwrap_throw();
+                Ok(())
+            })
+            .build_global()
+            .unwrap_throw();
+    }
+}
+
+// entry point of each worker; never returns while the pool is alive
+#[wasm_bindgen]
+pub fn start_thread(receiver: *const Threads) {
+    let receiver = unsafe { &*receiver };
+    let thread = receiver.lock().unwrap_throw().recv().unwrap_throw();
+    thread.run();
+}
+
+// starts the pool that the parallel passes run on; call it once after init, off the main
+// thread, in a cross-origin isolated page so memory can be shared
+#[wasm_bindgen]
+pub fn init_thread_pool(threads: usize) -> Promise {
+    let (sender, receiver) = channel();
+    start_workers(
+        wasm_bindgen::module(),
+        wasm_bindgen::memory(),
+        PoolBuilder {
+            threads: threads.max(1),
+            sender,
+            receiver: Mutex::new(receiver),
+        },
+    )
+}
@@ -1,12 +1,17 @@
-import init, { find_document, extract_document, Quad as WasmQuad } from '../../pkg/scanner';
+import init, { Detector, DetectOptions, extract_document, Quad as WasmQuad } from '../../pkg/scanner';
 import { Message, Messages } from './ipc'
 
 declare const self: DedicatedWorkerGlobalScope
 
+// kept across camera frames so detection reuses its buffers
+let detector: Detector | undefined;
+
 const handle = <T extends Message>(message: T): { result: Messages[T['type']][1]; transfer?: Transferable[] } => {
   if (message.type == 'find-document') {
     // const ts = performance.now();
-    const quad = find_document(message.data);
+    detector ||= new Detector(new DetectOptions());
+    const doc = detector.detect(message.data);
+    const quad = doc && doc.quad;
     // console.log('find_document:', (performance.now() - ts) + 'ms')
     return {
       result: quad && {
@@ -0,0 +1,2 @@
+// every export is defined in the core behind its wasm feature
+pub use scanner::*;